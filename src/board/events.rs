@@ -1,48 +1,96 @@
+use super::history::Move;
 use super::Board;
 use crate::ext::vec2::*;
-use cursive::event::Key;
+use crate::keymap::Action;
+use crate::tile::Tile;
+use cursive::event::EventResult;
 use cursive::XY;
 
 impl Board {
-	pub fn on_click(&mut self, pos: Vec2) {
+	pub fn on_click(&mut self, pos: Vec2) -> EventResult {
 		// allow cancelling a confirmed selection by clicking on it
 		if self.confirmed_selection.map(|confirmed| confirmed == pos).unwrap_or(false) {
 			self.confirmed_selection = None;
 		}
 		// if the tile is empty, ignore the click (except for cancelling selections)
 		if !self.is_occupied(pos) {
-			return;
+			return EventResult::Consumed(None);
 		}
 		if self.confirmed_selection.is_some() {
 			self.tentative_selection = Some((std::time::Instant::now(), pos));
-			self.process_selections();
+			self.process_selections()
 		} else {
 			self.confirmed_selection = Some(pos);
 			self.tentative_selection = None;
+			EventResult::Consumed(None)
 		}
 	}
-	fn process_selections(&mut self) {
+	fn process_selections(&mut self) -> EventResult {
 		let start = self.confirmed_selection.expect("No confirmed selection");
 		let end = self.tentative_selection.expect("No tentative selection").1;
 
-		if self.at(start) != self.at(end) {
+		if !self.at(start).zip(self.at(end)).is_some_and(|(start, end)| start.matches(end)) {
 			self.confirmed_selection = Some(end);
 			self.tentative_selection = None;
-			return;
+			return EventResult::Consumed(None);
 		}
-		let path = self.tiles.find_path(start, end);
+		let path = self.tiles.find_path(start, end, self.rules.max_turns);
 		if let Some(path) = path {
-			*self.at_mut(start).expect("Confirmed selection out of range") = None;
-			*self.at_mut(end).expect("Tentative selection out of range") = None;
-			self.shown_path = Some((std::time::Instant::now(), path));
+			let tiles = [(start, self.at(start).expect("Confirmed selection is empty")), (end, self.at(end).expect("Tentative selection is empty"))];
+			let points = self.score_match(&path);
+			self.remove_pair(tiles, path.clone());
+			let falls = if self.rules.gravity { self.apply_gravity([start.x, end.x]) } else { Vec::new() };
+			self.history.push(Move::Match { tiles, path, points, falls });
 			self.tentative_selection = Some((std::time::Instant::now(), end));
-			self.confirmed_selection = None;
+			self.after_match()
 		} else {
 			self.confirmed_selection = Some(end);
 			self.tentative_selection = None;
+			EventResult::Consumed(None)
+		}
+	}
+	fn remove_pair(&mut self, tiles: [(Vec2, Tile); 2], path: Vec<Vec2>) {
+		for (pos, _) in tiles {
+			*self.at_mut(pos).expect("Matched tile out of range") = None;
+		}
+		self.clear_transient();
+		self.shown_path = Some((std::time::Instant::now(), path));
+	}
+	/// Lets the tiles in the given columns fall to the bottom of the board, returning which tiles moved where.
+	fn apply_gravity(&mut self, columns: [usize; 2]) -> Vec<(Vec2, Vec2)> {
+		self.shown_tiles = Some(self.tiles.clone());
+		let mut falls = Vec::new();
+		let columns = if columns[0] == columns[1] { &columns[..1] } else { &columns[..] };
+		// the rows of the playfield, leaving out the empty border
+		let border = self.rules.border();
+		let rows = border..=self.size().y + border - 1;
+		for &x in columns {
+			let mut bottom = *rows.end();
+			for y in rows.clone().rev() {
+				let from = Vec2::new(x, y);
+				if self.is_occupied(from) {
+					let to = Vec2::new(x, bottom);
+					if from != to {
+						self.tiles.swap(from, to);
+						falls.push((from, to));
+					}
+					bottom -= 1;
+				}
+			}
+		}
+		falls
+	}
+	/// Checks whether the game has ended after a pair was removed.
+	fn after_match(&mut self) -> EventResult {
+		if self.is_cleared() {
+			EventResult::with_cb(crate::game::won)
+		} else if self.is_stuck() {
+			EventResult::with_cb(crate::game::stuck)
+		} else {
+			EventResult::Consumed(None)
 		}
 	}
-	fn confirm_selection(&mut self) {
+	fn confirm_selection(&mut self) -> EventResult {
 		match (self.tentative_selection, self.confirmed_selection) {
 			(Some((_, tentative)), None) => {
 				if self.is_occupied(tentative) {
@@ -52,37 +100,142 @@ impl Board {
 			}
 			(None, None) => (),
 			(None, Some(_)) => (),
-			(Some(_), Some(_)) => self.process_selections(),
+			(Some(_), Some(_)) => return self.process_selections(),
 		}
+		EventResult::Consumed(None)
 	}
 	fn undo_selection(&mut self) {
 		self.confirmed_selection = None;
 	}
-	fn move_selection(&mut self, x_delta: isize, y_delta: isize) {
-		// TODO don't allow the selection to end up in the padding border
-		if let Some(ref mut tentative_selection) = self.tentative_selection {
-			tentative_selection.1.move_wrapping(XY::new(x_delta, y_delta), self.tiles.size());
-			tentative_selection.0 = std::time::Instant::now();
-		} else {
-			self.tentative_selection = Some((std::time::Instant::now(), self.confirmed_selection.unwrap_or_else(|| Vec2::new(1, 1))));
+	fn undo(&mut self) {
+		let undone = match self.history.undo() {
+			Some(undone) => undone.clone(),
+			None => return,
+		};
+		match undone {
+			Move::Match { tiles, falls, .. } => {
+				for &(from, to) in falls.iter().rev() {
+					self.tiles.swap(from, to);
+				}
+				for (pos, tile) in tiles {
+					*self.at_mut(pos).expect("Matched tile out of range") = Some(tile);
+				}
+			}
+			Move::Reshuffle { positions, before, .. } => self.place(&positions, &before),
+		}
+		self.clear_transient();
+		self.combo = None;
+		self.stats.undos += 1;
+	}
+	fn redo(&mut self) -> EventResult {
+		let redone = match self.history.redo() {
+			Some(redone) => redone.clone(),
+			None => return EventResult::Consumed(None),
+		};
+		match redone {
+			Move::Match { tiles, path, falls, .. } => {
+				self.remove_pair(tiles, path);
+				if !falls.is_empty() {
+					self.shown_tiles = Some(self.tiles.clone());
+				}
+				for (from, to) in falls {
+					self.tiles.swap(from, to);
+				}
+				self.after_match()
+			}
+			Move::Reshuffle { positions, after, .. } => {
+				self.place(&positions, &after);
+				self.clear_transient();
+				EventResult::Consumed(None)
+			}
 		}
-		// if the new tentative selection would overlap the confirmed selection, move it again
-		if self.tentative_selection.map(|(_, sel)| sel) == self.confirmed_selection {
-			let (ref mut updated, ref mut selection) = self.tentative_selection.as_mut().unwrap(); // guaranteed to be occupied as we filled it in the previous block
-			selection.move_wrapping(XY::new(x_delta, y_delta), self.tiles.size());
-			*updated = std::time::Instant::now();
+	}
+	/// Shows one currently connectable pair and counts it against the player.
+	fn show_hint(&mut self) {
+		if let Some((_, _, path)) = self.tiles.available_moves(self.rules.max_turns).next() {
+			self.hint = Some((std::time::Instant::now(), path));
+			self.combo = None;
+			self.stats.hints += 1;
 		}
 	}
-	pub fn on_key(&mut self, key: Key) -> bool {
-		match key {
-			Key::Right => self.move_selection(1, 0),
-			Key::Left => self.move_selection(-1, 0),
-			Key::Up => self.move_selection(0, -1),
-			Key::Down => self.move_selection(0, 1),
-			Key::Enter => self.confirm_selection(),
-			Key::Backspace => self.undo_selection(),
-			_ => return false,
+	/// Moves the keyboard cursor to the position `next` picks, or puts it at the top left of the playfield if it was not shown yet.
+	fn move_selection(&mut self, next: impl Fn(&Self, Vec2) -> Vec2) {
+		let selection = match (self.tentative_selection, self.confirmed_selection) {
+			(Some((_, tentative)), _) => next(self, tentative),
+			// moved away from below the confirmed selection just after this
+			(None, Some(confirmed)) => confirmed,
+			(None, None) => Vec2::new(self.rules.border(), self.rules.border()),
+		};
+		// the cursor never rests on the confirmed selection, so move it again
+		let selection = if Some(selection) == self.confirmed_selection { next(self, selection) } else { selection };
+		self.tentative_selection = Some((std::time::Instant::now(), selection));
+	}
+	/// The position one step away, wrapping around at the edges of the playfield so the cursor never enters the empty border.
+	fn step(&self, from: Vec2, delta: XY<isize>) -> Vec2 {
+		let border = self.rules.border();
+		let mut inner = from.saturating_sub((border, border));
+		inner.move_wrapping(delta, self.size());
+		inner + (border, border)
+	}
+	/// The nearest tile in a direction, wrapping around at the edges of the playfield, or `from` itself if there is none.
+	///
+	/// Tiles in the same row or column come first, then those in the rows or columns next to it and so on, so empty stretches of the board are skipped over.
+	fn jump(&self, from: Vec2, delta: XY<isize>) -> Vec2 {
+		let border = self.rules.border();
+		let size = self.size();
+		// how far a tile is in the direction of movement, and how far off to the side
+		let distance = |pos: Vec2| {
+			let (along, across, length) = if delta.x != 0 {
+				(pos.x as isize - from.x as isize, pos.y.abs_diff(from.y), size.x as isize)
+			} else {
+				(pos.y as isize - from.y as isize, pos.x.abs_diff(from.x), size.y as isize)
+			};
+			let along = (along * (delta.x + delta.y).signum()).rem_euclid(length);
+			// a tile straight across from the cursor can only be reached by wrapping all the way around
+			(across, if along == 0 { length } else { along })
+		};
+		(0..size.y)
+			.flat_map(|y| (0..size.x).map(move |x| Vec2::new(x, y) + (border, border)))
+			.filter(|&pos| pos != from && Some(pos) != self.confirmed_selection && self.is_occupied(pos))
+			.min_by_key(|&pos| distance(pos))
+			.unwrap_or(from)
+	}
+	/// Carries out an action that concerns the board, ignoring the ones that are handled by the rest of the game.
+	pub fn on_action(&mut self, action: Action) -> EventResult {
+		// while paused only the actions that do not touch the tiles are allowed
+		if self.paused && !matches!(action, Action::ZoomIn | Action::ZoomOut | Action::Help) {
+			return EventResult::Ignored;
+		}
+		match action {
+			Action::Right => self.move_selection(|board, from| board.step(from, XY::new(1, 0))),
+			Action::Left => self.move_selection(|board, from| board.step(from, XY::new(-1, 0))),
+			Action::Up => self.move_selection(|board, from| board.step(from, XY::new(0, -1))),
+			Action::Down => self.move_selection(|board, from| board.step(from, XY::new(0, 1))),
+			Action::JumpRight => self.move_selection(|board, from| board.jump(from, XY::new(1, 0))),
+			Action::JumpLeft => self.move_selection(|board, from| board.jump(from, XY::new(-1, 0))),
+			Action::JumpUp => self.move_selection(|board, from| board.jump(from, XY::new(0, -1))),
+			Action::JumpDown => self.move_selection(|board, from| board.jump(from, XY::new(0, 1))),
+			Action::Select => return self.confirm_selection(),
+			Action::Cancel => self.undo_selection(),
+			Action::ZoomIn => self.set_zoom(self.zoom.zoom_in()),
+			Action::ZoomOut => self.set_zoom(self.zoom.zoom_out()),
+			Action::Hint => self.show_hint(),
+			Action::Undo => self.undo(),
+			Action::Redo => return self.redo(),
+			Action::Dump => {
+				let text = self.to_text();
+				return EventResult::with_cb(move |siv| crate::game::dump(siv, &text));
+			}
+			Action::CheckWinnable => {
+				let (tiles, rules) = (self.tiles.clone(), self.rules);
+				return EventResult::with_cb(move |siv| crate::game::check_winnable(siv, tiles.clone(), rules));
+			}
+			Action::Help => {
+				let text = self.keymap.help();
+				return EventResult::with_cb(move |siv| crate::game::help(siv, &text));
+			}
+			Action::Restart | Action::NewGame | Action::Statistics | Action::Theme | Action::Pause | Action::Quit => return EventResult::Ignored,
 		};
-		true
+		EventResult::Consumed(None)
 	}
 }
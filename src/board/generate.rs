@@ -0,0 +1,99 @@
+use super::{Rules, Tile};
+use crate::ext::vec2::*;
+use crate::matrix::Matrix;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+// how many times to start over when the backwards construction paints itself into a corner
+const SOLVABLE_ATTEMPTS: usize = 100;
+
+/// Deals the tiles of `unbordered` into a new matrix, bordered as `rules` require, that is guaranteed to be solvable under those rules except for gravity.
+///
+/// The deal is built backwards: pairs are placed one at a time, each only at two positions that can be connected given the pairs placed before it.
+/// Removing the pairs in the reverse order of placement is therefore always a valid solution.
+/// Returns `None` if every attempt got stuck, which happens for tiny boards and often when paths may not turn at all.
+pub(super) fn solvable(unbordered: &Matrix<Option<Tile>>, rules: Rules, rng: &mut impl Rng) -> Option<Matrix<Option<Tile>>> {
+	let mut tiles: Vec<Tile> = unbordered.iter().filter_map(|(_, tile)| *tile).collect();
+	// keep matching tiles next to each other, so every pair below is made of two tiles that match
+	tiles.sort_by_key(|&tile| (tile.group() as u8, tile as u8));
+	let mut pairs: Vec<[Tile; 2]> = tiles.chunks_exact(2).map(|pair| [pair[0], pair[1]]).collect();
+
+	(0..SOLVABLE_ATTEMPTS).find_map(|_| {
+		pairs.shuffle(rng);
+		try_place_pairs(unbordered.size(), &pairs, rules, rng)
+	})
+}
+
+fn try_place_pairs(size: Vec2, pairs: &[[Tile; 2]], rules: Rules, rng: &mut impl Rng) -> Option<Matrix<Option<Tile>>> {
+	let mut matrix = Matrix::new(size, vec![None; size.area()]);
+	rules.add_border(&mut matrix);
+	let border = rules.border();
+	let mut empty: Vec<Vec2> = (border..size.height() + border).flat_map(|y| (border..size.width() + border).map(move |x| Vec2::new(x, y))).collect();
+
+	for &pair in pairs {
+		empty.shuffle(rng);
+		// fill the most enclosed cells first, since they only get harder to reach as the board fills up
+		empty.sort_by_key(|&pos| empty_neighbors(&matrix, pos));
+		let (first, second) = find_placement(&mut matrix, &empty, pair, rules.max_turns)?;
+		// remove the larger index first so the smaller one stays valid
+		empty.remove(first.max(second));
+		empty.remove(first.min(second));
+	}
+	Some(matrix)
+}
+
+fn empty_neighbors(matrix: &Matrix<Option<Tile>>, pos: Vec2) -> usize {
+	// without a border, wrapping around at the edge gives a position outside the matrix, which does not count as empty
+	[pos.map_x(|x| x.wrapping_sub(1)), pos.map_x(|x| x + 1), pos.map_y(|y| y.wrapping_sub(1)), pos.map_y(|y| y + 1)]
+		.into_iter()
+		.filter(|&neighbor| matrix.get(neighbor) == Some(&None))
+		.count()
+}
+
+/// Returns true if some empty cell in the playfield is completely surrounded by tiles, as nothing could ever be placed there.
+fn has_enclosed_cell(matrix: &Matrix<Option<Tile>>, empty: &[Vec2]) -> bool {
+	empty.iter().any(|&pos| matrix.get(pos) == Some(&None) && empty_neighbors(matrix, pos) == 0)
+}
+
+/// Places `pair` at two connectable positions from `empty` and returns their indices, or leaves the matrix untouched if there are none.
+fn find_placement(matrix: &mut Matrix<Option<Tile>>, empty: &[Vec2], pair: [Tile; 2], max_turns: usize) -> Option<(usize, usize)> {
+	for (first, &first_pos) in empty.iter().enumerate() {
+		*matrix.get_mut(first_pos).unwrap() = Some(pair[0]);
+		for (second, &second_pos) in empty.iter().enumerate().skip(first + 1) {
+			*matrix.get_mut(second_pos).unwrap() = Some(pair[1]);
+			if matrix.find_path(first_pos, second_pos, max_turns).is_some() && !has_enclosed_cell(matrix, empty) {
+				return Some((first, second));
+			}
+			*matrix.get_mut(second_pos).unwrap() = None;
+		}
+		*matrix.get_mut(first_pos).unwrap() = None;
+	}
+	None
+}
+
+#[cfg(test)]
+mod test {
+	use crate::board::Rules;
+	use crate::ext::vec2::*;
+	use crate::matrix::Matrix;
+	use crate::tile::{Tile, TileSet};
+	use rand::rngs::StdRng;
+	use rand::SeedableRng;
+
+	#[test]
+	fn solvable_keeps_tiles() {
+		let size = Vec2::new(18, 8);
+		let tiles: Vec<Option<Tile>> = TileSet::Traditional.groups().into_iter().flatten().map(Some).collect();
+		let unbordered = Matrix::new(size, tiles);
+		let dealt = super::solvable(&unbordered, Rules::default(), &mut StdRng::seed_from_u64(0)).expect("Deal succeeds");
+
+		assert_eq!(dealt.size(), Vec2::new(20, 10));
+		let is_border = |pos: Vec2| pos.x == 0 || pos.y == 0 || pos.x == 19 || pos.y == 9;
+		assert!(dealt.iter().filter(|&(pos, _)| is_border(pos)).all(|(_, tile)| tile.is_none()));
+		let mut expected: Vec<u8> = unbordered.iter().map(|(_, tile)| tile.unwrap() as u8).collect();
+		let mut actual: Vec<u8> = dealt.iter().filter(|&(pos, _)| !is_border(pos)).map(|(_, tile)| tile.expect("Playfield is full") as u8).collect();
+		expected.sort_unstable();
+		actual.sort_unstable();
+		assert_eq!(expected, actual);
+	}
+}
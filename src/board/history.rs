@@ -0,0 +1,83 @@
+use crate::ext::vec2::*;
+use crate::tile::Tile;
+
+/// One change to the board that can be undone and redone.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Move {
+	/// A matching pair was removed along the given path, earning `points`.
+	///
+	/// With gravity, the tiles above then fell from the first position of each of `falls` to the second, in order.
+	Match { tiles: [(Vec2, Tile); 2], path: Vec<Vec2>, points: u32, falls: Vec<(Vec2, Vec2)> },
+	/// The tiles at `positions` were rearranged from `before` to `after`.
+	Reshuffle { positions: Vec<Vec2>, before: Vec<Tile>, after: Vec<Tile> },
+}
+
+/// The moves made so far, plus the ones that were undone and can still be redone.
+#[derive(Default)]
+pub struct History {
+	pub(super) done: Vec<Move>,
+	pub(super) undone: Vec<Move>,
+}
+
+impl History {
+	/// Records a new move, which discards anything that could have been redone.
+	pub fn push(&mut self, made: Move) {
+		self.done.push(made);
+		self.undone.clear();
+	}
+	/// Moves the last move to the redo stack and returns it so it can be reverted.
+	pub fn undo(&mut self) -> Option<&Move> {
+		let undone = self.done.pop()?;
+		self.undone.push(undone);
+		self.undone.last()
+	}
+	/// Moves the last undone move back to the history and returns it so it can be reapplied.
+	pub fn redo(&mut self) -> Option<&Move> {
+		let redone = self.undone.pop()?;
+		self.done.push(redone);
+		self.done.last()
+	}
+	/// The number of pairs removed so far.
+	pub fn moves(&self) -> usize {
+		self.done.iter().filter(|made| matches!(made, Move::Match { .. })).count()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::{History, Move};
+	use crate::tile::Tile;
+	use cursive::Vec2;
+
+	fn pair(x: usize) -> Move {
+		Move::Match {
+			tiles: [(Vec2::new(x, 0), Tile::Blank), (Vec2::new(x, 1), Tile::Blank)],
+			path: vec![Vec2::new(x, 0), Vec2::new(x, 1)],
+			points: 10,
+			falls: Vec::new(),
+		}
+	}
+
+	#[test]
+	fn undo_redo() {
+		let mut history = History::default();
+		history.push(pair(0));
+		history.push(pair(1));
+		assert_eq!(history.moves(), 2);
+		assert_eq!(history.undo(), Some(&pair(1)));
+		assert_eq!(history.undo(), Some(&pair(0)));
+		assert_eq!(history.undo(), None);
+		assert_eq!(history.moves(), 0);
+		assert_eq!(history.redo(), Some(&pair(0)));
+		assert_eq!(history.moves(), 1);
+	}
+	#[test]
+	fn push_clears_redo() {
+		let mut history = History::default();
+		history.push(pair(0));
+		history.undo();
+		history.push(pair(1));
+		assert_eq!(history.redo(), None);
+		assert_eq!(history.moves(), 1);
+	}
+}
@@ -1,67 +1,268 @@
 use crate::ext::vec2::*;
+use crate::keymap::Keymap;
 use crate::matrix::Matrix;
-use crate::tile::Tile;
+use crate::theme::BoardStyles;
+use crate::tile::{Glyphs, Tile, TileSet};
+use anyhow::{ensure, Context};
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
 
 mod center_view;
 mod events;
+mod generate;
+mod history;
 mod path;
+mod save;
+mod score;
+mod solver;
+pub mod text;
 mod view;
 
 pub use center_view::CenterView;
+use history::{History, Move};
+pub use save::SavedBoard;
+pub use solver::Solution;
+
+/// Per-game statistics shown when the game ends.
+#[derive(Clone, Copy, Default, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Stats {
+	pub moves: usize,
+	pub hints: usize,
+	pub shuffles: usize,
+	pub undos: usize,
+}
+
+/// Rule variants that change how pairs can be removed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
+// rules missing from older saves keep their defaults
+#[serde(default)]
+pub struct Rules {
+	/// After a pair is removed, the tiles above it fall down to fill the gap.
+	pub gravity: bool,
+	/// How many times the path between a pair may change direction.
+	pub max_turns: usize,
+	/// Paths may run through the empty corridor around the outside of the board.
+	pub outer_corridor: bool,
+}
+
+impl Default for Rules {
+	fn default() -> Self {
+		Self {
+			gravity: false,
+			max_turns: 2,
+			outer_corridor: true,
+		}
+	}
+}
+
+impl Rules {
+	/// The width of the empty border around the playfield.
+	///
+	/// Without the outer corridor the board has no border at all, so paths cannot leave the playfield.
+	pub fn border(self) -> usize {
+		usize::from(self.outer_corridor)
+	}
+	/// Adds the empty border to a playfield, if these rules have one.
+	pub fn add_border(self, tiles: &mut Matrix<Option<Tile>>) {
+		if self.outer_corridor {
+			tiles.add_border(None);
+		}
+	}
+}
+
+/// How the tiles are arranged when a new board is created.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, clap::ValueEnum)]
+pub enum Deal {
+	/// Tiles are shuffled at random, so the board may not be solvable.
+	#[default]
+	Random,
+	/// Tiles are placed so that at least one solution is guaranteed to exist, although not with gravity.
+	Solvable,
+}
+
+/// How large tiles are drawn.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, clap::ValueEnum)]
+pub enum Zoom {
+	/// One character per tile.
+	#[default]
+	Small,
+	/// Tiles with a top edge and sides, three columns wide and two rows high.
+	Medium,
+	/// Boxed tiles, five columns wide and three rows high.
+	Large,
+}
+
+impl Zoom {
+	fn zoom_in(self) -> Self {
+		match self {
+			Self::Small => Self::Medium,
+			Self::Medium | Self::Large => Self::Large,
+		}
+	}
+	fn zoom_out(self) -> Self {
+		match self {
+			Self::Small | Self::Medium => Self::Small,
+			Self::Large => Self::Medium,
+		}
+	}
+}
 
 pub struct Board {
 	tiles: Matrix<Option<Tile>>,
+	rules: Rules,
+	glyphs: Glyphs,
+	zoom: Zoom,
+	styles: BoardStyles,
+	keymap: Keymap,
+	// set when the size of the view changed, until it is laid out again
+	needs_relayout: bool,
+	// the tiles are hidden and cannot be played while the game is paused
+	paused: bool,
 	confirmed_selection: Option<Vec2>,
 	// the Instant stores when the selection was last updated and is used for blinking
 	tentative_selection: Option<(std::time::Instant, Vec2)>,
 	// the Instant stores when the match with the shown path was made and is used for fading
 	shown_path: Option<(std::time::Instant, Vec<Vec2>)>,
+	// the board as it was when the shown path was made, before any tiles fell, so the path is drawn where it was found
+	shown_tiles: Option<Matrix<Option<Tile>>>,
+	// the Instant stores when the hint was requested and is used for fading
+	hint: Option<(std::time::Instant, Vec<Vec2>)>,
+	history: History,
+	// the pairs removed in quick succession just now, for the combo bonus
+	combo: Option<score::Combo>,
+	// the seed the deal was made from, shown so the deal can be shared
+	seed: u64,
+	// seeded when the board is created and also used for reshuffles, so replaying a seed with the same moves gives the same game.
+	// This is the generator behind `StdRng`, which cannot be saved, so a resumed game keeps reshuffling the same way
+	rng: ChaCha12Rng,
+	// `moves` is left at zero here and filled in from the history when requested
+	stats: Stats,
 }
 
 impl Board {
 	pub const DEFAULT_SIZE: Vec2 = Vec2 { x: 18, y: 8 };
 
-	const SHUFFLE_PASSES: usize = 100;
-
-	pub fn size(&self) -> Vec2 {
-		self.tiles.size()
-	}
+	// how long a hint stays visible, in milliseconds
+	const HINT_DURATION: u128 = 1500;
+	// how many times to retry a reshuffle that still leaves no moves before giving up
+	const RESHUFFLE_ATTEMPTS: usize = 100;
 
-	fn tiles_unshuffled(repeats: usize) -> Vec<Option<Tile>> {
-		let mut ret = Vec::with_capacity(Tile::NUM_TILES * repeats);
-		for _ in 0..repeats {
-			ret.extend(Tile::all().into_iter().map(Some));
+	/// Picks the tiles for a board with `area` cells, in sorted order.
+	///
+	/// Tiles come in groups of four that match each other, like a real set, plus a single pair if the area is not a multiple of four.
+	/// When the board is too small for the whole set, the groups are picked at random, and on larger boards every group is used as evenly as possible.
+	fn tiles_unshuffled(area: usize, tile_set: TileSet, rng: &mut ChaCha12Rng) -> Vec<Option<Tile>> {
+		let mut groups = tile_set.groups();
+		groups.shuffle(rng);
+		let mut ret = Vec::with_capacity(area);
+		for (index, group) in groups.iter().cycle().take(area.div_ceil(4)).enumerate() {
+			let copies = if index < area / 4 { 4 } else { 2 };
+			ret.extend(group[..copies].iter().copied().map(Some));
 		}
 		ret
 	}
-	fn shuffle(&mut self) {
-		let size = self.size();
-		for _ in 0..Self::SHUFFLE_PASSES {
-			let a = size.random_within();
-			let b = size.random_within();
 
-			self.tiles.swap(a, b);
+	/// Rearranges the remaining tiles among their current positions, retrying until at least one move is available.
+	pub fn reshuffle(&mut self) {
+		let positions: Vec<Vec2> = self.tiles.iter().filter(|(_, tile)| tile.is_some()).map(|(pos, _)| pos).collect();
+		let before: Vec<Tile> = positions.iter().filter_map(|&pos| self.at(pos)).collect();
+		let mut after = before.clone();
+		for _ in 0..Self::RESHUFFLE_ATTEMPTS {
+			after.shuffle(&mut self.rng);
+			self.place(&positions, &after);
+			if !self.is_stuck() {
+				break;
+			}
+		}
+		self.clear_transient();
+		self.history.push(Move::Reshuffle { positions, before, after });
+		self.stats.shuffles += 1;
+	}
+	fn place(&mut self, positions: &[Vec2], tiles: &[Tile]) {
+		for (&pos, &tile) in positions.iter().zip(tiles) {
+			*self.at_mut(pos).expect("Position out of range") = Some(tile);
 		}
 	}
+	/// Clears selections and any path or hint being shown, since they may no longer apply after the board changes.
+	fn clear_transient(&mut self) {
+		self.confirmed_selection = None;
+		self.shown_path = None;
+		self.shown_tiles = None;
+		self.hint = None;
+	}
 
-	pub fn new(size: Vec2) -> Self {
-		let total_tiles = size.area();
-		assert!(total_tiles % Tile::NUM_TILES == 0);
-		let mut ret = Self {
-			tiles: Matrix::new(size, Self::tiles_unshuffled(total_tiles / Tile::NUM_TILES)),
-			confirmed_selection: None,
-			tentative_selection: None,
-			shown_path: None,
+	/// Checks that a board of this size can be dealt.
+	pub fn check_size(size: Vec2) -> anyhow::Result<()> {
+		ensure!(size.x > 0 && size.y > 0, "The board must be at least 1x1");
+		ensure!(
+			size.area().is_multiple_of(2),
+			"A {}x{} board has {} cells, but the number of cells must be even so that every tile has a partner",
+			size.x,
+			size.y,
+			size.area()
+		);
+		Ok(())
+	}
+
+	pub fn new(size: Vec2, deal: Deal, tile_set: TileSet, rules: Rules, seed: u64) -> anyhow::Result<Self> {
+		Self::check_size(size)?;
+		let mut rng = ChaCha12Rng::seed_from_u64(seed);
+		let mut tiles = Self::tiles_unshuffled(size.area(), tile_set, &mut rng);
+		tiles.shuffle(&mut rng);
+		let mut tiles = Matrix::new(size, tiles);
+		let tiles = match deal {
+			Deal::Random => {
+				rules.add_border(&mut tiles);
+				tiles
+			}
+			// handing out a random deal instead would break the promise of a solvable one
+			Deal::Solvable => generate::solvable(&tiles, rules, &mut rng).context("Could not find a solvable deal for this size and these rules. Allowing more turns or a random deal will help.")?,
 		};
-		ret.shuffle();
-		ret.tiles.add_border(None);
-		ret
+		Ok(Self::with_tiles(tiles, rules, seed, rng))
 	}
-}
 
-impl Default for Board {
-	fn default() -> Self {
-		Self::new(Self::DEFAULT_SIZE)
+	/// Starts a game from a board file, using `seed` for any reshuffles.
+	pub fn load(path: &std::path::Path, rules: Rules, seed: u64) -> anyhow::Result<Self> {
+		Ok(Self::unbordered(text::read(path)?, rules, seed))
+	}
+	/// Starts a game from a board in the text format, using `seed` for any reshuffles.
+	#[cfg(test)]
+	pub fn from_text(text: &str, rules: Rules, seed: u64) -> anyhow::Result<Self> {
+		Ok(Self::unbordered(text::parse(text)?, rules, seed))
+	}
+	/// Starts a game from the tiles of a playfield that does not have its border yet.
+	fn unbordered(mut tiles: Matrix<Option<Tile>>, rules: Rules, seed: u64) -> Self {
+		rules.add_border(&mut tiles);
+		Self::with_tiles(tiles, rules, seed, ChaCha12Rng::seed_from_u64(seed))
+	}
+	/// Formats the current position in the text format, without the border.
+	pub fn to_text(&self) -> String {
+		let border = self.rules.border();
+		text::write(self.rows().skip(border).take(self.size().y).map(|row| &row[border..row.len() - border]))
+	}
+
+	fn with_tiles(tiles: Matrix<Option<Tile>>, rules: Rules, seed: u64, rng: ChaCha12Rng) -> Self {
+		Self {
+			tiles,
+			rules,
+			glyphs: Glyphs::default(),
+			zoom: Zoom::default(),
+			styles: BoardStyles::default(),
+			keymap: Keymap::default(),
+			needs_relayout: false,
+			paused: false,
+			confirmed_selection: None,
+			tentative_selection: None,
+			shown_path: None,
+			shown_tiles: None,
+			hint: None,
+			history: History::default(),
+			combo: None,
+			seed,
+			rng,
+			stats: Stats::default(),
+		}
 	}
 }
 
@@ -81,4 +282,226 @@ impl Board {
 	pub fn rows(&self) -> impl Iterator<Item = &[Option<Tile>]> {
 		self.tiles.rows()
 	}
+	/// Returns true once every tile has been removed from the board.
+	pub fn is_cleared(&self) -> bool {
+		self.rows().flatten().all(Option::is_none)
+	}
+	/// Returns true if the position is one end of the currently shown hint.
+	pub fn is_hinted(&self, pos: Vec2) -> bool {
+		match self.hint {
+			Some((shown_time, ref path)) => shown_time.elapsed().as_millis() < Self::HINT_DURATION && (path.first() == Some(&pos) || path.last() == Some(&pos)),
+			None => false,
+		}
+	}
+	/// The size of the board, not counting the empty border.
+	pub fn size(&self) -> Vec2 {
+		let border = self.rules.border();
+		self.tiles.size().saturating_sub((border * 2, border * 2))
+	}
+	pub fn rules(&self) -> Rules {
+		self.rules
+	}
+	pub fn set_glyphs(&mut self, glyphs: Glyphs) {
+		self.glyphs = glyphs;
+		self.needs_relayout = true;
+	}
+	pub fn set_zoom(&mut self, zoom: Zoom) {
+		self.zoom = zoom;
+		self.needs_relayout = true;
+	}
+	pub fn set_styles(&mut self, styles: BoardStyles) {
+		self.styles = styles;
+	}
+	pub fn is_paused(&self) -> bool {
+		self.paused
+	}
+	pub fn set_paused(&mut self, paused: bool) {
+		self.paused = paused;
+	}
+	pub fn set_keymap(&mut self, keymap: Keymap) {
+		self.keymap = keymap;
+	}
+	/// Draws this board the same way as `other` and gives it the same keys, for a board that replaces it.
+	pub fn copy_preferences(&mut self, other: &Board) {
+		self.set_glyphs(other.glyphs);
+		self.set_zoom(other.zoom);
+		self.set_styles(other.styles);
+		self.set_keymap(other.keymap.clone());
+	}
+	pub fn seed(&self) -> u64 {
+		self.seed
+	}
+	pub fn stats(&self) -> Stats {
+		Stats { moves: self.history.moves(), ..self.stats }
+	}
+	/// Returns true if tiles remain but no pair of them can be connected.
+	pub fn is_stuck(&self) -> bool {
+		!self.is_cleared() && self.tiles.available_moves(self.rules.max_turns).next().is_none()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::{Board, Deal, Rules, Solution};
+	use crate::ext::vec2::*;
+	use crate::keymap::Action;
+	use crate::tile::{Tile, TileSet};
+
+	const DEALS: u64 = 2000;
+
+	/// Pearson's chi-squared statistic for counts that should all be equal.
+	fn chi_squared(counts: &[usize]) -> f64 {
+		let expected = counts.iter().sum::<usize>() as f64 / counts.len() as f64;
+		counts.iter().map(|&count| (count as f64 - expected).powi(2) / expected).sum()
+	}
+
+	/// The tiles of a random deal without the empty border, in reading order.
+	fn dealt(seed: u64) -> Vec<Tile> {
+		Board::new(Board::DEFAULT_SIZE, Deal::Random, TileSet::Classic, Rules::default(), seed).unwrap().rows().flatten().filter_map(|&tile| tile).collect()
+	}
+
+	#[test]
+	fn same_seed_same_deal() {
+		for deal in [Deal::Random, Deal::Solvable] {
+			let a = Board::new(Board::DEFAULT_SIZE, deal, TileSet::Classic, Rules::default(), 1234).unwrap();
+			let b = Board::new(Board::DEFAULT_SIZE, deal, TileSet::Classic, Rules::default(), 1234).unwrap();
+			let c = Board::new(Board::DEFAULT_SIZE, deal, TileSet::Classic, Rules::default(), 4321).unwrap();
+			assert!(a.rows().eq(b.rows()));
+			assert!(!a.rows().eq(c.rows()));
+		}
+	}
+	#[test]
+	fn text_round_trip() {
+		let board = Board::new(Vec2::new(6, 4), Deal::Random, TileSet::Classic, Rules::default(), 0).unwrap();
+		let text = board.to_text();
+		assert_eq!(text.lines().count(), 4);
+		assert!(text.lines().all(|line| line.len() == 6));
+		assert!(Board::from_text(&text, Rules::default(), 0).unwrap().rows().eq(board.rows()));
+	}
+	#[test]
+	fn gravity_and_undo() {
+		let text = "A.\n0.\nA0\n";
+		let mut board = Board::from_text(text, Rules { gravity: true, ..Rules::default() }, 0).unwrap();
+		board.on_click(Vec2::new(1, 2));
+		board.on_click(Vec2::new(2, 3));
+		assert_eq!(board.to_text(), "..\nA.\nA.\n");
+		board.on_action(Action::Undo);
+		assert_eq!(board.to_text(), text);
+		board.on_action(Action::Redo);
+		assert_eq!(board.to_text(), "..\nA.\nA.\n");
+	}
+	#[test]
+	fn no_outer_corridor() {
+		let rules = Rules { outer_corridor: false, ..Rules::default() };
+		let mut board = Board::from_text("0AA0\n", rules, 0).unwrap();
+		assert_eq!(board.size(), Vec2::new(4, 1));
+		board.on_click(Vec2::new(0, 0));
+		board.on_click(Vec2::new(3, 0));
+		assert_eq!(board.to_text(), "0AA0\n");
+		assert!(Board::from_text("0A0A\n", rules, 0).unwrap().is_stuck());
+		assert!(!Board::from_text("0A0A\n", Rules::default(), 0).unwrap().is_stuck());
+
+		let mut board = Board::from_text("0AA0\n", Rules::default(), 0).unwrap();
+		board.on_click(Vec2::new(1, 1));
+		board.on_click(Vec2::new(4, 1));
+		assert_eq!(board.to_text(), ".AA.\n");
+
+		let board = Board::new(Vec2::new(6, 4), Deal::Solvable, TileSet::Classic, rules, 0).unwrap();
+		assert_eq!(board.size(), Vec2::new(6, 4));
+		assert!(board.rows().flatten().all(Option::is_some));
+	}
+	#[test]
+	fn solvable_or_error() {
+		let rules = Rules { max_turns: 0, ..Rules::default() };
+		// no solvable deal is found for seed 2, which used to fall back to the random deal
+		assert!(Board::new(Board::DEFAULT_SIZE, Deal::Solvable, TileSet::Classic, rules, 2).is_err());
+		let board = Board::new(Board::DEFAULT_SIZE, Deal::Solvable, TileSet::Classic, rules, 0).unwrap();
+		assert!(matches!(board.tiles.solve(rules.max_turns), Solution::Found(_)));
+	}
+	#[test]
+	fn keyboard_cursor() {
+		let mut board = Board::from_text("A..B\n....\n..A.\n", Rules::default(), 0).unwrap();
+		let mut cursor = |action| {
+			board.on_action(action);
+			board.tentative_selection.map(|(_, pos)| pos)
+		};
+		// stepping wraps around the playfield without entering the border
+		assert_eq!(cursor(Action::Left), Some(Vec2::new(1, 1)));
+		assert_eq!(cursor(Action::Left), Some(Vec2::new(4, 1)));
+		assert_eq!(cursor(Action::Up), Some(Vec2::new(4, 3)));
+		// jumping skips empty positions, and looks in the nearest other rows or columns when there is nothing straight ahead
+		assert_eq!(cursor(Action::JumpLeft), Some(Vec2::new(3, 3)));
+		assert_eq!(cursor(Action::JumpLeft), Some(Vec2::new(1, 1)));
+		assert_eq!(cursor(Action::JumpDown), Some(Vec2::new(3, 3)));
+		// and never lands on the confirmed selection
+		assert_eq!(cursor(Action::Select), None);
+		assert_eq!(cursor(Action::JumpUp), Some(Vec2::new(4, 1)));
+	}
+	#[test]
+	fn paused_ignores_moves() {
+		let mut board = Board::from_text("A..A\n....\n", Rules::default(), 0).unwrap();
+		board.set_paused(true);
+		for action in [Action::Left, Action::JumpRight, Action::Select, Action::Hint, Action::Undo] {
+			assert!(!board.on_action(action).is_consumed(), "{:?}", action);
+		}
+		assert_eq!(board.tentative_selection, None);
+		assert_eq!(board.confirmed_selection, None);
+		assert!(board.hint.is_none());
+		// the view can still be zoomed
+		assert!(board.on_action(Action::ZoomIn).is_consumed());
+		board.set_paused(false);
+		board.on_action(Action::Left);
+		board.on_action(Action::Select);
+		board.on_action(Action::JumpRight);
+		board.on_action(Action::Select);
+		assert_eq!(board.stats().moves, 1);
+	}
+	#[test]
+	fn balanced_kinds() {
+		for (width, height) in [(1, 2), (6, 4), (5, 2), (7, 6), (18, 8), (20, 10)] {
+			for (deal, tile_set) in [Deal::Random, Deal::Solvable].into_iter().flat_map(|deal| [(deal, TileSet::Classic), (deal, TileSet::Traditional)]) {
+				let size = Vec2::new(width, height);
+				let tiles = Board::new(size, deal, tile_set, Rules::default(), 0).unwrap();
+				// matching flowers or seasons count as one kind
+				let mut counts = [0; Tile::NUM_TILES];
+				for tile in tiles.rows().flatten().flatten() {
+					counts[tile.group() as usize] += 1;
+				}
+				assert_eq!(counts.iter().sum::<usize>(), size.area());
+				assert!(counts.iter().all(|count| count % 2 == 0), "{:?}", counts);
+				let used = counts.iter().filter(|&&count| count > 0);
+				assert!(used.clone().max().unwrap() - used.min().unwrap() <= 4, "{:?}", counts);
+			}
+		}
+	}
+	#[test]
+	fn positions_are_uniform() {
+		// where the copies of a single kind end up, over many deals
+		let kind = Tile::all()[0];
+		let mut counts = vec![0; Board::DEFAULT_SIZE.area()];
+		for seed in 0..DEALS {
+			for (index, tile) in dealt(seed).into_iter().enumerate() {
+				if tile == kind {
+					counts[index] += 1;
+				}
+			}
+		}
+		// the 99.99th percentile of the chi-squared distribution with 143 degrees of freedom
+		assert!(chi_squared(&counts) < 214.7, "{:?}", counts);
+	}
+	#[test]
+	fn kinds_are_uniform() {
+		// which kind ends up in the first and last cells, since those are where a biased shuffle leaves the sorted order most visible
+		let kinds = TileSet::Classic.groups().len();
+		let mut first = vec![0; kinds];
+		let mut last = vec![0; kinds];
+		for seed in 0..DEALS {
+			let tiles = dealt(seed);
+			first[tiles[0] as usize] += 1;
+			last[tiles[tiles.len() - 1] as usize] += 1;
+		}
+		// the 99.99th percentile of the chi-squared distribution with 35 degrees of freedom
+		assert!(chi_squared(&first) < 75.1, "{:?}", first);
+		assert!(chi_squared(&last) < 75.1, "{:?}", last);
+	}
 }
@@ -3,8 +3,6 @@ use crate::ext::vec2::*;
 use crate::matrix::Matrix;
 
 impl Matrix<Option<Tile>> {
-	const MAX_STEPS: usize = 2 + 2; // 2 turns + start + end
-
 	fn successors(&self, pos: Vec2, goal_tile: Tile) -> Vec<Vec2> {
 		let mut ret = Vec::new();
 		let size = self.size();
@@ -18,7 +16,7 @@ impl Matrix<Option<Tile>> {
 					if current_tile.is_none() {
 						ret.push(current_pos);
 						continue;
-					} else if current_tile == Some(goal_tile) {
+					} else if current_tile.is_some_and(|tile| tile.matches(goal_tile)) {
 						ret.push(current_pos);
 						break;
 					} else {
@@ -28,33 +26,92 @@ impl Matrix<Option<Tile>> {
 			};
 		}
 		// left
-		add_items_for!((0..pos.x).into_iter().rev().map(with_x));
+		add_items_for!((0..pos.x).rev().map(with_x));
 		// right
-		add_items_for!(((pos.x + 1)..size.width()).into_iter().map(with_x));
+		add_items_for!(((pos.x + 1)..size.width()).map(with_x));
 		// above
-		add_items_for!((0..pos.y).into_iter().rev().map(with_y));
+		add_items_for!((0..pos.y).rev().map(with_y));
 		// below
-		add_items_for!((pos.y + 1..size.height()).into_iter().map(with_y));
+		add_items_for!((pos.y + 1..size.height()).map(with_y));
 
 		ret
 	}
+	/// Returns every pair of matching tiles that can currently be connected, along with the path between them.
+	///
+	/// The iterator is lazy, so checking whether any move is left only searches until the first one is found.
+	pub(super) fn available_moves(&self, max_turns: usize) -> impl Iterator<Item = (Vec2, Vec2, Vec<Vec2>)> + '_ {
+		let occupied: Vec<(Vec2, Tile)> = self.iter().filter_map(|(pos, tile)| tile.map(|tile| (pos, tile))).collect();
+		let count = occupied.len();
+		(0..count).flat_map(move |first| (first + 1..count).map(move |second| (first, second))).filter_map(move |(first, second)| {
+			let (start, start_tile) = occupied[first];
+			let (end, end_tile) = occupied[second];
+			if !start_tile.matches(end_tile) {
+				return None;
+			}
+			self.find_path(start, end, max_turns).map(|path| (start, end, path))
+		})
+	}
+	/// Returns true if a path could be found, like `find_path` but much faster since the path itself is not needed.
+	///
+	/// The last leg of any path is a straight line into `end`, so instead of searching all the way there,
+	/// the search from `start` stops as soon as it reaches a position in a straight line from `end`.
+	pub(super) fn is_connectable(&self, start: Vec2, end: Vec2, max_turns: usize) -> bool {
+		let goal = Cross::around(self, end);
+		if goal.is_next_to(start) {
+			return true;
+		}
+		// the first leg out of `start` and the last one into `goal` take up one turn between them
+		max_turns > 0 && self.reaches(start, &goal, max_turns - 1, None)
+	}
+	/// Walks straight lines from `pos`, recursing with one turn fewer at each empty position, until one of them meets `goal`.
+	fn reaches(&self, pos: Vec2, goal: &Cross, turns_left: usize, came_horizontally: Option<bool>) -> bool {
+		let size = self.size();
+		let directions: &[(isize, isize)] = match came_horizontally {
+			None => &[(-1, 0), (1, 0), (0, -1), (0, 1)],
+			Some(true) => &[(0, -1), (0, 1)],
+			Some(false) => &[(-1, 0), (1, 0)],
+		};
+		for &(dx, dy) in directions {
+			let mut current = pos;
+			loop {
+				let (x, y) = (current.x as isize + dx, current.y as isize + dy);
+				if x < 0 || y < 0 || x as usize >= size.width() || y as usize >= size.height() {
+					break;
+				}
+				current = Vec2::new(x as usize, y as usize);
+				if goal.contains(current) {
+					return true;
+				}
+				if self.get(current).unwrap().is_some() {
+					break;
+				}
+				if turns_left > 0 && self.reaches(current, goal, turns_left - 1, Some(dx != 0)) {
+					return true;
+				}
+			}
+		}
+		false
+	}
 	/// Returns the corners of the path including the start and end, if a path could be found.
-	pub(super) fn find_path(&self, start: Vec2, end: Vec2) -> Option<Vec<Vec2>> {
-		use std::collections::{HashMap, HashSet, VecDeque};
+	pub(super) fn find_path(&self, start: Vec2, end: Vec2, max_turns: usize) -> Option<Vec<Vec2>> {
+		use std::collections::VecDeque;
 
+		// every turn adds a corner to the path, on top of the start and end
+		let max_steps = max_turns + 2;
 		let mut queue = VecDeque::from([(start, 1)]);
-		let mut visited = HashSet::from([start]);
-		let mut traceback = HashMap::new();
+		// the previous corner for every visited position, which doubles as the visited set
+		let mut traceback: Matrix<Option<Vec2>> = Matrix::new(self.size(), vec![None; self.size().area()]);
+		*traceback.get_mut(start).unwrap() = Some(start);
 		let goal_tile = self.get(end).unwrap().unwrap();
 
-		fn trace_answer(mut current: Vec2, start: Vec2, traceback: &HashMap<Vec2, Vec2>) -> Vec<Vec2> {
-			let mut ret = Vec::with_capacity(Matrix::MAX_STEPS);
+		fn trace_answer(mut current: Vec2, start: Vec2, traceback: &Matrix<Option<Vec2>>) -> Vec<Vec2> {
+			let mut ret = Vec::new();
 			loop {
 				ret.push(current);
 				if current == start {
 					break;
 				}
-				current = traceback[&current];
+				current = traceback.get(current).unwrap().unwrap();
 			}
 			ret.reverse();
 			ret
@@ -68,21 +125,62 @@ impl Matrix<Option<Tile>> {
 			if current == end {
 				break Some(trace_answer(current, start, &traceback));
 			}
-			if steps >= Self::MAX_STEPS {
-				break None;
+			// other nodes at the same depth may still be the goal, so keep draining the queue
+			if steps >= max_steps {
+				continue;
+			}
+			// a matching tile other than the goal blocks the path like any other tile
+			if current != start && self.get(current).unwrap().is_some() {
+				continue;
 			}
 			for successor in self.successors(current, goal_tile) {
-				if visited.contains(&successor) {
+				let previous = traceback.get_mut(successor).unwrap();
+				if previous.is_some() {
 					continue;
 				}
-				traceback.insert(successor, current);
-				visited.insert(successor);
+				*previous = Some(current);
 				queue.push_back((successor, steps + 1));
 			}
 		}
 	}
 }
 
+/// The empty positions in a straight line from a position, along with the position itself.
+struct Cross {
+	center: Vec2,
+	// inclusive ranges of the empty positions in the center's row and column
+	columns: (usize, usize),
+	rows: (usize, usize),
+}
+
+impl Cross {
+	fn around(matrix: &Matrix<Option<Tile>>, center: Vec2) -> Self {
+		let is_empty = |pos: Vec2| matrix.get(pos).unwrap().is_none();
+		let mut columns = (center.x, center.x);
+		while columns.0 > 0 && is_empty(center.with_x(columns.0 - 1)) {
+			columns.0 -= 1;
+		}
+		while columns.1 + 1 < matrix.size().width() && is_empty(center.with_x(columns.1 + 1)) {
+			columns.1 += 1;
+		}
+		let mut rows = (center.y, center.y);
+		while rows.0 > 0 && is_empty(center.with_y(rows.0 - 1)) {
+			rows.0 -= 1;
+		}
+		while rows.1 + 1 < matrix.size().height() && is_empty(center.with_y(rows.1 + 1)) {
+			rows.1 += 1;
+		}
+		Self { center, columns, rows }
+	}
+	fn contains(&self, pos: Vec2) -> bool {
+		(pos.y == self.center.y && (self.columns.0..=self.columns.1).contains(&pos.x)) || (pos.x == self.center.x && (self.rows.0..=self.rows.1).contains(&pos.y))
+	}
+	/// Returns true if `pos` is the occupied position right past the end of one of the lines, meaning the two can be connected directly.
+	fn is_next_to(&self, pos: Vec2) -> bool {
+		(pos.y == self.center.y && (pos.x + 1 == self.columns.0 || pos.x == self.columns.1 + 1)) || (pos.x == self.center.x && (pos.y + 1 == self.rows.0 || pos.y == self.rows.1 + 1))
+	}
+}
+
 #[cfg(test)]
 mod test {
 	use crate::matrix::Matrix;
@@ -147,21 +245,21 @@ mod test {
 		let matrix = Matrix::new(Vec2::new(3, 3), vec![Some(Tile::Blank), None, Some(Tile::Blank), None, None, None, None, None, None]);
 		let start = Vec2::new(0, 0);
 		let end = Vec2::new(2, 0);
-		check_solution(start, end, &matrix.find_path(start, end).expect("Solution exists"), 0, &matrix);
+		check_solution(start, end, &matrix.find_path(start, end, 2).expect("Solution exists"), 0, &matrix);
 	}
 	#[test]
 	fn around() {
 		let matrix = Matrix::new(Vec2::new(3, 3), vec![Some(Tile::Blank), Some(Tile::Sticks1), Some(Tile::Blank), None, None, None, None, None, None]);
 		let start = Vec2::new(0, 0);
 		let end = Vec2::new(2, 0);
-		check_solution(start, end, &matrix.find_path(start, end).expect("Solution exists"), 2, &matrix);
+		check_solution(start, end, &matrix.find_path(start, end, 2).expect("Solution exists"), 2, &matrix);
 	}
 	#[test]
 	fn zigzag() {
 		let matrix = Matrix::new(Vec2::new(3, 3), vec![Some(Tile::Blank), Some(Tile::Sticks1), None, None, None, None, Some(Tile::Sticks1), Some(Tile::Blank), None]);
 		let start = Vec2::new(0, 0);
 		let end = Vec2::new(1, 2);
-		check_solution(start, end, &matrix.find_path(start, end).expect("Solution exists"), 2, &matrix);
+		check_solution(start, end, &matrix.find_path(start, end, 2).expect("Solution exists"), 2, &matrix);
 	}
 	#[test]
 	fn no_path() {
@@ -179,12 +277,59 @@ mod test {
 				Some(Tile::Sticks1),
 			],
 		);
-		assert_eq!(matrix.find_path(Vec2::new(0, 0), Vec2::new(1, 2)), None);
+		assert_eq!(matrix.find_path(Vec2::new(0, 0), Vec2::new(1, 2), 2), None);
 	}
 	#[test]
 	pub fn barely_too_long() {
 		let matrix = Matrix::new(Vec2::new(3, 3), vec![None, None, None, None, Some(Tile::Sticks1), Some(Tile::Blank), None, Some(Tile::Blank), Some(Tile::Sticks1)]);
-		assert_eq!(matrix.find_path(Vec2::new(1, 2), Vec2::new(2, 1)), None);
+		assert_eq!(matrix.find_path(Vec2::new(1, 2), Vec2::new(2, 1), 2), None);
+	}
+	#[test]
+	fn through_matching_tile() {
+		let matrix = Matrix::new(Vec2::new(3, 1), vec![Some(Tile::Blank), Some(Tile::Blank), Some(Tile::Blank)]);
+		assert_eq!(matrix.find_path(Vec2::new(0, 0), Vec2::new(2, 0), 2), None);
+	}
+	#[test]
+	fn is_connectable_agrees() {
+		use rand::rngs::StdRng;
+		use rand::seq::SliceRandom;
+		use rand::{Rng, SeedableRng};
+
+		let mut rng = StdRng::seed_from_u64(0);
+		for _ in 0..200 {
+			let mut tiles: Vec<Option<Tile>> = (0..16).map(|_| if rng.gen_bool(0.5) { None } else { Some(Tile::Sticks1) }).collect();
+			tiles.extend([Some(Tile::Blank), Some(Tile::Blank)]);
+			tiles.shuffle(&mut rng);
+			let matrix = Matrix::new(Vec2::new(6, 3), tiles);
+			let blanks: Vec<Vec2> = matrix.iter().filter(|&(_, tile)| *tile == Some(Tile::Blank)).map(|(pos, _)| pos).collect();
+			for max_turns in 0..=3 {
+				assert_eq!(matrix.is_connectable(blanks[0], blanks[1], max_turns), matrix.find_path(blanks[0], blanks[1], max_turns).is_some());
+			}
+		}
+	}
+	#[test]
+	fn available_moves() {
+		let matrix = Matrix::new(Vec2::new(3, 3), vec![Some(Tile::Blank), Some(Tile::Sticks1), Some(Tile::Blank), None, None, None, Some(Tile::Sticks1), None, Some(Tile::Number1)]);
+		let moves: Vec<_> = matrix.available_moves(2).map(|(start, end, _)| (start, end)).collect();
+		assert_eq!(moves, vec![(Vec2::new(0, 0), Vec2::new(2, 0)), (Vec2::new(1, 0), Vec2::new(0, 2))]);
+	}
+	#[test]
+	fn no_available_moves() {
+		let matrix = Matrix::new(
+			Vec2::new(3, 3),
+			vec![
+				Some(Tile::Blank),
+				Some(Tile::Sticks1),
+				Some(Tile::Sticks2),
+				Some(Tile::Sticks2),
+				Some(Tile::Number1),
+				Some(Tile::Number2),
+				Some(Tile::Sticks1),
+				Some(Tile::Blank),
+				Some(Tile::Number3),
+			],
+		);
+		assert_eq!(matrix.available_moves(2).next(), None);
 	}
 	#[test]
 	fn too_long() {
@@ -209,6 +354,6 @@ mod test {
 				Some(Tile::Blank),
 			],
 		);
-		assert_eq!(matrix.find_path(Vec2::new(0, 0), Vec2::new(3, 3)), None);
+		assert_eq!(matrix.find_path(Vec2::new(0, 0), Vec2::new(3, 3), 2), None);
 	}
 }
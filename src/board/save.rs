@@ -0,0 +1,197 @@
+//! The serializable form of a board, used to save a game in progress.
+
+use super::history::{History, Move};
+use super::{Board, Rules, Stats};
+use crate::ext::vec2::*;
+use crate::matrix::Matrix;
+use crate::tile::Tile;
+use anyhow::ensure;
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+// `Vec2` does not implement the serde traits, so positions are stored as tuples
+type Position = (usize, usize);
+
+#[derive(Clone, Serialize, Deserialize)]
+enum SavedMove {
+	Match {
+		tiles: [(Position, Tile); 2],
+		path: Vec<Position>,
+		points: u32,
+		#[serde(default)]
+		falls: Vec<(Position, Position)>,
+	},
+	Reshuffle {
+		positions: Vec<Position>,
+		before: Vec<Tile>,
+		after: Vec<Tile>,
+	},
+}
+
+/// Everything needed to continue a game where it was left off, apart from the clock.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedBoard {
+	size: Position,
+	// includes the empty border
+	tiles: Vec<Option<Tile>>,
+	// saves from before rule variants existed used the default rules
+	#[serde(default)]
+	rules: Rules,
+	seed: u64,
+	// saves from before the generator was stored carry on from a fresh one for the same seed
+	#[serde(default)]
+	rng: Option<ChaCha12Rng>,
+	confirmed_selection: Option<Position>,
+	tentative_selection: Option<Position>,
+	done: Vec<SavedMove>,
+	undone: Vec<SavedMove>,
+	stats: Stats,
+}
+
+fn position(pos: Vec2) -> Position {
+	(pos.x, pos.y)
+}
+
+impl From<&Move> for SavedMove {
+	fn from(made: &Move) -> Self {
+		match made {
+			Move::Match { tiles, path, points, falls } => Self::Match {
+				tiles: tiles.map(|(pos, tile)| (position(pos), tile)),
+				path: path.iter().copied().map(position).collect(),
+				points: *points,
+				falls: falls.iter().map(|&(from, to)| (position(from), position(to))).collect(),
+			},
+			Move::Reshuffle { positions, before, after } => Self::Reshuffle {
+				positions: positions.iter().copied().map(position).collect(),
+				before: before.clone(),
+				after: after.clone(),
+			},
+		}
+	}
+}
+
+impl From<SavedMove> for Move {
+	fn from(saved: SavedMove) -> Self {
+		match saved {
+			SavedMove::Match { tiles, path, points, falls } => Self::Match {
+				tiles: tiles.map(|(pos, tile)| (Vec2::from(pos), tile)),
+				path: path.into_iter().map(Vec2::from).collect(),
+				points,
+				falls: falls.into_iter().map(|(from, to)| (Vec2::from(from), Vec2::from(to))).collect(),
+			},
+			SavedMove::Reshuffle { positions, before, after } => Self::Reshuffle {
+				positions: positions.into_iter().map(Vec2::from).collect(),
+				before,
+				after,
+			},
+		}
+	}
+}
+
+impl SavedMove {
+	fn positions(&self) -> Vec<Position> {
+		match self {
+			Self::Match { tiles, path, falls, .. } => tiles.iter().map(|&(pos, _)| pos).chain(path.iter().copied()).chain(falls.iter().flat_map(|&(from, to)| [from, to])).collect(),
+			Self::Reshuffle { positions, .. } => positions.clone(),
+		}
+	}
+}
+
+impl Board {
+	pub fn save(&self) -> SavedBoard {
+		SavedBoard {
+			size: position(self.tiles.size()),
+			tiles: self.tiles.iter().map(|(_, &tile)| tile).collect(),
+			rules: self.rules,
+			seed: self.seed,
+			rng: Some(self.rng.clone()),
+			confirmed_selection: self.confirmed_selection.map(position),
+			tentative_selection: self.tentative_selection.map(|(_, pos)| position(pos)),
+			done: self.history.done.iter().map(SavedMove::from).collect(),
+			undone: self.history.undone.iter().map(SavedMove::from).collect(),
+			stats: self.stats,
+		}
+	}
+
+	/// Recreates a saved board, checking that the save is consistent so a damaged file cannot cause a panic later on.
+	pub fn restore(saved: SavedBoard) -> anyhow::Result<Self> {
+		let size = Vec2::from(saved.size);
+		ensure!(saved.tiles.len() == size.area(), "The saved board has {} tiles, but its size is {}x{}", saved.tiles.len(), size.x, size.y);
+		// the playfield inside the border must not be empty, as a lot of code takes at least one row and column for granted
+		let border = saved.rules.border();
+		ensure!(size.x > 2 * border && size.y > 2 * border, "The saved board is too small at {}x{}", size.x, size.y);
+		let in_range = saved
+			.confirmed_selection
+			.iter()
+			.chain(&saved.tentative_selection)
+			.copied()
+			.chain(saved.done.iter().chain(&saved.undone).flat_map(SavedMove::positions))
+			.all(|pos| size.contains(Vec2::from(pos)));
+		ensure!(in_range, "The saved game refers to positions outside the board");
+
+		let rng = saved.rng.unwrap_or_else(|| ChaCha12Rng::seed_from_u64(saved.seed));
+		let mut ret = Self::with_tiles(Matrix::new(size, saved.tiles), saved.rules, saved.seed, rng);
+		ret.confirmed_selection = saved.confirmed_selection.map(Vec2::from);
+		ret.tentative_selection = saved.tentative_selection.map(|pos| (Instant::now(), Vec2::from(pos)));
+		ret.history = History {
+			done: saved.done.into_iter().map(Move::from).collect(),
+			undone: saved.undone.into_iter().map(Move::from).collect(),
+		};
+		ret.stats = saved.stats;
+		Ok(ret)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::super::{Board, Deal, Rules};
+	use crate::keymap::Action;
+	use crate::tile::TileSet;
+
+	#[test]
+	fn round_trip() {
+		let mut board = Board::new(Board::DEFAULT_SIZE, Deal::Solvable, TileSet::Traditional, Rules { gravity: true, ..Rules::default() }, 7).unwrap();
+		for _ in 0..3 {
+			let (start, end, _) = board.tiles.available_moves(2).next().unwrap();
+			board.on_click(start);
+			board.on_click(end);
+		}
+		board.on_action(Action::Undo);
+		let (selected, _, _) = board.tiles.available_moves(2).next().unwrap();
+		board.on_click(selected);
+
+		let json = serde_json::to_string(&board.save()).unwrap();
+		let restored = Board::restore(serde_json::from_str(&json).unwrap()).unwrap();
+		assert!(restored.rows().eq(board.rows()));
+		assert_eq!(restored.seed, board.seed);
+		assert_eq!(restored.confirmed_selection, board.confirmed_selection);
+		assert_eq!(restored.history.done, board.history.done);
+		assert_eq!(restored.history.undone, board.history.undone);
+		assert_eq!(restored.stats().moves, 2);
+		assert_eq!(restored.stats().undos, 1);
+		assert_eq!(restored.score(), board.score());
+		assert_eq!(restored.rules, board.rules);
+
+		// reshuffling after resuming gives what the game would have given without the break
+		let mut restored = restored;
+		board.reshuffle();
+		restored.reshuffle();
+		assert!(restored.rows().eq(board.rows()));
+	}
+	#[test]
+	fn damaged_sizes() {
+		let board = Board::new(Board::DEFAULT_SIZE, Deal::Random, TileSet::Classic, Rules::default(), 0).unwrap();
+		let mut json: serde_json::Value = serde_json::to_value(board.save()).unwrap();
+		json["size"] = serde_json::json!([0, 0]);
+		json["tiles"] = serde_json::json!([]);
+		json["confirmed_selection"] = serde_json::Value::Null;
+		json["tentative_selection"] = serde_json::Value::Null;
+		assert!(Board::restore(serde_json::from_value(json.clone()).unwrap()).is_err());
+		// with the outer corridor, two columns are only the border
+		json["size"] = serde_json::json!([2, 3]);
+		json["tiles"] = serde_json::json!([null, null, null, null, null, null]);
+		assert!(Board::restore(serde_json::from_value(json).unwrap()).is_err());
+	}
+}
@@ -0,0 +1,89 @@
+//! Points for the current game, from the pairs removed and the help used along the way.
+
+use super::history::Move;
+use super::Board;
+use crate::ext::vec2::*;
+use std::time::{Duration, Instant};
+
+/// Points for every pair removed.
+const PAIR: u32 = 10;
+/// Extra points for a pair connected by a straight line.
+const STRAIGHT: u32 = 10;
+/// Extra points for each pair in a row removed within `COMBO_WINDOW` of the previous one.
+const COMBO: u32 = 5;
+const COMBO_WINDOW: Duration = Duration::from_secs(3);
+const HINT_PENALTY: i64 = 20;
+const UNDO_PENALTY: i64 = 10;
+const RESHUFFLE_PENALTY: i64 = 50;
+
+/// A run of pairs removed in quick succession.
+#[derive(Clone, Copy)]
+pub(super) struct Combo {
+	last_match: Instant,
+	// the number of pairs in the run so far
+	length: u32,
+}
+
+impl Board {
+	/// Works out the points for a pair that was just connected along `path`, continuing or starting a combo.
+	pub(super) fn score_match(&mut self, path: &[Vec2]) -> u32 {
+		let now = Instant::now();
+		let length = match self.combo {
+			Some(combo) if now.duration_since(combo.last_match) <= COMBO_WINDOW => combo.length + 1,
+			_ => 1,
+		};
+		self.combo = Some(Combo { last_match: now, length });
+		// the path holds the two ends plus one position per turn
+		let straight = if path.len() == 2 { STRAIGHT } else { 0 };
+		PAIR + straight + COMBO * (length - 1)
+	}
+
+	/// The points for the pairs currently removed, minus the penalties for hints, undos and reshuffles.
+	pub fn score(&self) -> i64 {
+		let earned: i64 = self
+			.history
+			.done
+			.iter()
+			.map(|made| match made {
+				Move::Match { points, .. } => i64::from(*points),
+				Move::Reshuffle { .. } => 0,
+			})
+			.sum();
+		let stats = self.stats();
+		earned - HINT_PENALTY * stats.hints as i64 - UNDO_PENALTY * stats.undos as i64 - RESHUFFLE_PENALTY * stats.shuffles as i64
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::super::{Board, Rules};
+	use super::{COMBO, HINT_PENALTY, PAIR, RESHUFFLE_PENALTY, STRAIGHT, UNDO_PENALTY};
+	use crate::ext::vec2::*;
+	use crate::keymap::Action;
+
+	#[test]
+	fn combo_and_penalties() {
+		let mut board = Board::from_text("0A.\n.A0\n", Rules::default(), 0).unwrap();
+		// a straight pair, then a pair with one turn that continues the combo
+		board.on_click(Vec2::new(2, 1));
+		board.on_click(Vec2::new(2, 2));
+		assert_eq!(board.score(), (PAIR + STRAIGHT) as i64);
+		board.on_click(Vec2::new(1, 1));
+		board.on_click(Vec2::new(3, 2));
+		let both = (PAIR + STRAIGHT + PAIR + COMBO) as i64;
+		assert_eq!(board.score(), both);
+		board.on_action(Action::Undo);
+		assert_eq!(board.score(), (PAIR + STRAIGHT) as i64 - UNDO_PENALTY);
+		board.on_action(Action::Hint);
+		board.on_action(Action::Redo);
+		assert_eq!(board.score(), both - UNDO_PENALTY - HINT_PENALTY);
+	}
+	#[test]
+	fn reshuffle_penalty() {
+		let mut board = Board::from_text("0A.\n.A0\n", Rules::default(), 0).unwrap();
+		board.reshuffle();
+		assert_eq!(board.score(), -RESHUFFLE_PENALTY);
+		board.reshuffle();
+		assert_eq!(board.score(), -2 * RESHUFFLE_PENALTY);
+	}
+}
@@ -0,0 +1,398 @@
+use super::Tile;
+use crate::ext::vec2::*;
+use crate::matrix::Matrix;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::collections::HashMap;
+
+type Pair = (Vec2, Vec2);
+
+/// The outcome of searching for a way to clear a board.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Solution {
+	/// The pairs to remove, in order.
+	Found(Vec<Pair>),
+	/// No sequence of removals clears the board.
+	Unsolvable,
+	/// The search gave up before it could decide, which keeps hard positions from taking minutes.
+	Unknown,
+}
+
+// how many positions the first search may visit before it starts over in a different order
+const FIRST_BUDGET: usize = 50;
+// how many positions may be visited in total, counted rather than timed so the result does not depend on the machine or the build.
+// A position on a full 18x8 board takes up to about 0.45ms in a release build (1.3ms in a debug build), so this gives up after about a quarter of a second.
+// Almost all random deals are decided well within it: of seeds 0 to 299, 8 need more and the hardest needs about 5500.
+const TOTAL_BUDGET: usize = 600;
+
+impl Matrix<Option<Tile>> {
+	/// Searches for a sequence of pair removals that clears the board.
+	///
+	/// The search starts over in a different order, with half as much budget again, whenever it runs out.
+	/// Depth-first search can get stuck for a long time on an unlucky early choice, and a new order usually avoids that.
+	pub fn solve(&self, max_turns: usize) -> Solution {
+		let mut solver = Solver {
+			matrix: self.clone(),
+			max_turns,
+			removed: Vec::new(),
+			forbidden: Vec::new(),
+			dead_ends: HashMap::new(),
+			nodes_left: 0,
+			out_of_budget: false,
+			// a fixed seed keeps the solutions reproducible
+			rng: StdRng::seed_from_u64(0),
+		};
+		let (mut budget, mut spent) = (FIRST_BUDGET, 0);
+		while spent < TOTAL_BUDGET {
+			solver.nodes_left = budget.min(TOTAL_BUDGET - spent);
+			solver.out_of_budget = false;
+			if solver.search() {
+				return Solution::Found(solver.removed.into_iter().map(|(start, end, _)| (start, end)).collect());
+			}
+			if !solver.out_of_budget {
+				return Solution::Unsolvable;
+			}
+			spent += budget;
+			budget += budget / 2;
+		}
+		Solution::Unknown
+	}
+}
+
+/// The remaining positions of one kind of tile, counting all flowers or all seasons as one kind, and which pairs of them can be removed right now.
+struct Kind {
+	positions: Vec<Vec2>,
+	available: Vec<Pair>,
+}
+
+/// A depth-first search over the ways each kind of tile can be paired up.
+///
+/// Removing a pair only ever frees up space, so it can never block a path between other tiles.
+/// If a solution pairs up two tiles that are connectable right now, removing them first is therefore also a solution.
+/// This means the search only has to branch on a single kind at a time: either one of its currently connectable pairs is removed now,
+/// or the solution pairs that kind up differently, in which case those pairs are forbidden from then on.
+struct Solver {
+	matrix: Matrix<Option<Tile>>,
+	max_turns: usize,
+	removed: Vec<(Vec2, Vec2, [Tile; 2])>,
+	forbidden: Vec<Pair>,
+	// for each set of occupied cells, the sets of excluded pairs that are known not to lead to a solution, kept when the search starts over
+	dead_ends: HashMap<Vec<u64>, Vec<Vec<Pair>>>,
+	// how many more positions the current search may visit
+	nodes_left: usize,
+	// set when the current search ran out of positions to visit, so its failures prove nothing
+	out_of_budget: bool,
+	// shuffles the order the search tries things in after starting over
+	rng: StdRng,
+}
+
+impl Solver {
+	/// Searches from the current position, leaving it unchanged unless a solution is found.
+	fn search(&mut self) -> bool {
+		let checkpoint = self.removed.len();
+		let forbidden_len = self.forbidden.len();
+		if self.search_inner() {
+			return true;
+		}
+		while self.removed.len() > checkpoint {
+			self.restore();
+		}
+		self.forbidden.truncate(forbidden_len);
+		false
+	}
+
+	fn search_inner(&mut self) -> bool {
+		if self.nodes_left == 0 {
+			self.out_of_budget = true;
+			return false;
+		}
+		self.nodes_left -= 1;
+		let kinds = loop {
+			let kinds = self.kinds();
+			let safe: Vec<Pair> = kinds.iter().flat_map(|kind| self.safe_moves(kind)).collect();
+			if safe.is_empty() {
+				break kinds;
+			}
+			for (start, end) in safe {
+				self.remove(start, end);
+			}
+		};
+
+		if kinds.is_empty() {
+			return true;
+		}
+		if !kinds.iter().all(|kind| self.can_pair_up(&kind.positions)) || self.is_hopeless(&kinds) {
+			return false;
+		}
+		let (occupancy, excluded) = self.key(&kinds);
+		// excluding more pairs can only make things harder, so any dead end with a subset of the excluded pairs applies here too
+		if let Some(dead) = self.dead_ends.get(&occupancy) {
+			if dead.iter().any(|dead| dead.iter().all(|pair| excluded.binary_search_by_key(&pair_key(pair), pair_key).is_ok())) {
+				return false;
+			}
+		}
+
+		// branch on the kind with the fewest options, counting the option of pairing it up some other way if that is still possible
+		let mut kinds = kinds;
+		kinds.shuffle(&mut self.rng);
+		let options = kinds
+			.iter()
+			.filter(|kind| !kind.available.is_empty())
+			.map(|kind| {
+				let can_avoid = kind.positions.len() > 4 || self.pairings(&kind.positions).iter().any(|pairing| pairing.iter().all(|pair| !kind.available.contains(pair)));
+				(&kind.available, can_avoid)
+			})
+			.min_by_key(|(available, can_avoid)| available.len() + usize::from(*can_avoid));
+		if let Some((options, can_avoid)) = options {
+			let mut shuffled = options.clone();
+			shuffled.shuffle(&mut self.rng);
+			for &(start, end) in &shuffled {
+				self.remove(start, end);
+				if self.search() {
+					return true;
+				}
+				self.restore();
+				if self.out_of_budget {
+					return false;
+				}
+			}
+			if can_avoid {
+				self.forbidden.extend(options);
+				if self.search() {
+					return true;
+				}
+			}
+		}
+		// a search that was cut short did not rule anything out
+		if !self.out_of_budget {
+			self.dead_ends.entry(occupancy).or_default().push(excluded);
+		}
+		false
+	}
+
+	fn kinds(&self) -> Vec<Kind> {
+		let mut by_kind: HashMap<Tile, Vec<Vec2>> = HashMap::new();
+		for (pos, tile) in self.matrix.iter() {
+			if let Some(tile) = *tile {
+				by_kind.entry(tile.group()).or_default().push(pos);
+			}
+		}
+		let mut kinds: Vec<Kind> = by_kind
+			.into_values()
+			.map(|positions| {
+				let available = all_pairs(&positions).filter(|&pair| !self.is_forbidden(pair) && self.matrix.is_connectable(pair.0, pair.1, self.max_turns)).collect();
+				Kind { positions, available }
+			})
+			.collect();
+		// keep the search order independent of the hash map's order so solutions are reproducible
+		kinds.sort_unstable_by_key(|kind| (kind.positions[0].y, kind.positions[0].x));
+		kinds
+	}
+
+	/// Finds removals that cannot hurt: either every pair in one way of pairing up a kind is connectable, or there is only one way left and some of its pairs are connectable.
+	fn safe_moves(&self, kind: &Kind) -> Vec<Pair> {
+		let pairings = self.pairings(&kind.positions);
+		if let Some(pairing) = pairings.iter().find(|pairing| pairing.iter().all(|pair| kind.available.contains(pair))) {
+			return pairing.clone();
+		}
+		match &pairings[..] {
+			[forced] => forced.iter().copied().filter(|pair| kind.available.contains(pair)).collect(),
+			_ => Vec::new(),
+		}
+	}
+
+	/// Lists the ways of splitting the remaining tiles of a kind into pairs that are not forbidden.
+	///
+	/// Kinds with more than four tiles left (only possible on large boards) are not enumerated and return no pairings.
+	fn pairings(&self, positions: &[Vec2]) -> Vec<Vec<Pair>> {
+		let candidates = match *positions {
+			[a, b] => vec![vec![(a, b)]],
+			[a, b, c, d] => vec![vec![(a, b), (c, d)], vec![(a, c), (b, d)], vec![(a, d), (b, c)]],
+			_ => return Vec::new(),
+		};
+		candidates.into_iter().filter(|pairing| pairing.iter().all(|&pair| !self.is_forbidden(pair))).collect()
+	}
+
+	/// Checks that the remaining tiles of a kind can still be split into pairs that are not forbidden.
+	fn can_pair_up(&self, positions: &[Vec2]) -> bool {
+		positions.len() > 4 || !self.pairings(positions).is_empty()
+	}
+
+	/// Checks a relaxed version of the game where a tile may leave as soon as it can reach any position where a partner is or was.
+	///
+	/// Every removal in a real game is also possible in the relaxed one, since the relaxed board never has more tiles in the way,
+	/// so if some tile can never leave even then, the position cannot be solved.
+	fn is_hopeless(&self, kinds: &[Kind]) -> bool {
+		let mut relaxed = self.matrix.clone();
+		// tiles that can already be removed for real can certainly leave in the relaxed game
+		for kind in kinds {
+			for &(start, end) in &kind.available {
+				*relaxed.get_mut(start).unwrap() = None;
+				*relaxed.get_mut(end).unwrap() = None;
+			}
+		}
+		let mut remaining: Vec<(Vec2, &[Vec2])> = kinds
+			.iter()
+			.flat_map(|kind| kind.positions.iter().map(move |&pos| (pos, &kind.positions[..])))
+			.filter(|&(pos, _)| relaxed.get(pos).unwrap().is_some())
+			.collect();
+		if remaining.is_empty() {
+			return false;
+		}
+		loop {
+			let before = remaining.len();
+			remaining.retain(|&(pos, partners)| {
+				let can_leave = partners.iter().any(|&partner| partner != pos && !self.is_forbidden((pos, partner)) && relaxed.is_connectable(pos, partner, self.max_turns));
+				if can_leave {
+					*relaxed.get_mut(pos).unwrap() = None;
+				}
+				!can_leave
+			});
+			if remaining.is_empty() {
+				return false;
+			}
+			if remaining.len() == before {
+				return true;
+			}
+		}
+	}
+
+	fn is_forbidden(&self, (start, end): Pair) -> bool {
+		self.forbidden.contains(&(start, end)) || self.forbidden.contains(&(end, start))
+	}
+
+	fn key(&self, kinds: &[Kind]) -> (Vec<u64>, Vec<Pair>) {
+		let mut occupancy = vec![0; self.matrix.size().area().div_ceil(64)];
+		for (index, (_, tile)) in self.matrix.iter().enumerate() {
+			if tile.is_some() {
+				occupancy[index / 64] |= 1 << (index % 64);
+			}
+		}
+		// only the ways of pairing up each kind that are still allowed matter, not which pairs were forbidden to get there
+		let mut excluded: Vec<Pair> = kinds.iter().flat_map(|kind| self.excluded_pairs(&kind.positions)).collect();
+		excluded.sort_unstable_by_key(pair_key);
+		(occupancy, excluded)
+	}
+
+	/// The pairs of a kind that cannot be used in any pairing that is still allowed.
+	fn excluded_pairs(&self, positions: &[Vec2]) -> Vec<Pair> {
+		if positions.len() > 4 {
+			return all_pairs(positions).filter(|&pair| self.is_forbidden(pair)).collect();
+		}
+		let pairings = self.pairings(positions);
+		all_pairs(positions).filter(|pair| !pairings.iter().any(|pairing| pairing.contains(pair))).collect()
+	}
+
+	fn remove(&mut self, start: Vec2, end: Vec2) {
+		let start_tile = self.matrix.get_mut(start).unwrap().take().expect("Removed tile is empty");
+		let end_tile = self.matrix.get_mut(end).unwrap().take().expect("Removed tile is empty");
+		self.removed.push((start, end, [start_tile, end_tile]));
+	}
+
+	fn restore(&mut self) {
+		let (start, end, [start_tile, end_tile]) = self.removed.pop().expect("Nothing to restore");
+		*self.matrix.get_mut(start).unwrap() = Some(start_tile);
+		*self.matrix.get_mut(end).unwrap() = Some(end_tile);
+	}
+}
+
+fn pair_key(&(start, end): &Pair) -> (usize, usize, usize, usize) {
+	(start.y, start.x, end.y, end.x)
+}
+
+fn all_pairs(positions: &[Vec2]) -> impl Iterator<Item = Pair> + '_ {
+	positions.iter().enumerate().flat_map(move |(index, &start)| positions[index + 1..].iter().map(move |&end| (start, end)))
+}
+
+#[cfg(test)]
+mod test {
+	use super::Solution;
+	use crate::ext::vec2::*;
+	use crate::matrix::Matrix;
+	use crate::tile::{Tile, TileSet};
+	use rand::rngs::StdRng;
+	use rand::SeedableRng;
+
+	/// Replays a solution on the board, checking every removal is valid and that the board ends up empty.
+	fn check_solution(mut matrix: Matrix<Option<Tile>>, solution: &[(Vec2, Vec2)]) {
+		for &(start, end) in solution {
+			assert!(matrix.get(start).unwrap().unwrap().matches(matrix.get(end).unwrap().unwrap()));
+			assert!(matrix.find_path(start, end, 2).is_some());
+			*matrix.get_mut(start).unwrap() = None;
+			*matrix.get_mut(end).unwrap() = None;
+		}
+		assert!(matrix.iter().all(|(_, tile)| tile.is_none()));
+	}
+
+	fn found(solution: Solution) -> Vec<(Vec2, Vec2)> {
+		match solution {
+			Solution::Found(solution) => solution,
+			other => panic!("Expected a solution, got {:?}", other),
+		}
+	}
+
+	fn bordered(rows: &[&str]) -> Matrix<Option<Tile>> {
+		let mut matrix = crate::board::text::parse(&rows.join("\n")).unwrap();
+		matrix.add_border(None);
+		matrix
+	}
+
+	#[test]
+	fn small() {
+		let matrix = bordered(&["0AA0", "1001"]);
+		check_solution(matrix.clone(), &found(matrix.solve(2)));
+	}
+	#[test]
+	fn crossed() {
+		let matrix = bordered(&["0A", "A0"]);
+		assert_eq!(matrix.solve(2), Solution::Unsolvable);
+	}
+	#[test]
+	fn flowers_and_seasons() {
+		let matrix = bordered(&["pQqR", "SrPs"]);
+		check_solution(matrix.clone(), &found(matrix.solve(2)));
+	}
+	#[test]
+	fn full_board() {
+		let matrix = bordered(&[
+			"^b53vdbcfeE<3Gaf37",
+			"2fHF<}giCaDGF&4Fi7",
+			"$9i5{>f6e0Ha^FCDhb",
+			"65e1937hB8E}g>IdiA",
+			"C8h0II2v49EA&^79$&",
+			"H>BA^6G{GE&{D62hA1",
+			"d4Hc8dD}<Cv}b1B><$",
+			"410{8aceI2g0$g5vcB",
+		]);
+		check_solution(matrix.clone(), &found(matrix.solve(2)));
+	}
+	#[test]
+	fn restarts() {
+		// a depth-first search without restarts took over 40 seconds on this deal
+		let board = crate::board::Board::new(crate::board::Board::DEFAULT_SIZE, crate::board::Deal::Random, TileSet::Classic, crate::board::Rules::default(), 156).unwrap();
+		let matrix = board.tiles.clone();
+		check_solution(matrix.clone(), &found(matrix.solve(2)));
+	}
+	#[test]
+	fn budget() {
+		// the budget should be enough to decide almost every random deal (measured: it decides all of these 50)
+		let unknown = (0..50)
+			.filter(|&seed| {
+				let board = crate::board::Board::new(crate::board::Board::DEFAULT_SIZE, crate::board::Deal::Random, TileSet::Classic, crate::board::Rules::default(), seed).unwrap();
+				board.tiles.solve(2) == Solution::Unknown
+			})
+			.count();
+		assert!(unknown <= 1, "Gave up on {} of 50 deals", unknown);
+	}
+	#[test]
+	fn generated() {
+		let size = Vec2::new(18, 8);
+		let tiles: Vec<Option<Tile>> = TileSet::Traditional.groups().into_iter().flatten().map(Some).collect();
+		for seed in 0..5 {
+			let mut rng = StdRng::seed_from_u64(seed);
+			let matrix = crate::board::generate::solvable(&Matrix::new(size, tiles.clone()), crate::board::Rules::default(), &mut rng).expect("Deal succeeds");
+			check_solution(matrix.clone(), &found(matrix.solve(2)));
+		}
+	}
+}
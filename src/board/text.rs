@@ -0,0 +1,75 @@
+//! The text format for boards: one line per row with one glyph per tile (as shown on screen) and `.` for empty cells.
+//!
+//! The empty border around the board is not part of the format.
+
+use crate::ext::vec2::*;
+use crate::matrix::Matrix;
+use crate::tile::Tile;
+use anyhow::{bail, ensure, Context};
+use std::path::Path;
+
+const EMPTY: char = '.';
+
+/// Reads a board file, returning the tiles without a border.
+pub fn read(path: &Path) -> anyhow::Result<Matrix<Option<Tile>>> {
+	let text = std::fs::read_to_string(path).with_context(|| format!("Could not read board file {:?}", path))?;
+	parse(&text).with_context(|| format!("Invalid board file {:?}", path))
+}
+
+pub fn parse(text: &str) -> anyhow::Result<Matrix<Option<Tile>>> {
+	let mut rows: Vec<&str> = text.lines().collect();
+	// an empty line anywhere else would be a missing row
+	while rows.last().is_some_and(|row| row.is_empty()) {
+		rows.pop();
+	}
+	let width = rows.first().map(|row| row.chars().count()).context("Board file is empty")?;
+	ensure!(width > 0, "Row 1 is empty");
+	let mut data = Vec::with_capacity(width * rows.len());
+	for (y, row) in rows.iter().enumerate() {
+		if row.chars().count() != width {
+			bail!("Row {} has a different length than the first row", y + 1);
+		}
+		for (x, glyph) in row.chars().enumerate() {
+			data.push(match glyph {
+				EMPTY => None,
+				_ => Some(glyph.encode_utf8(&mut [0; 4]).parse().with_context(|| format!("Invalid tile at row {}, column {}", y + 1, x + 1))?),
+			});
+		}
+	}
+	Ok(Matrix::new(Vec2::new(width, rows.len()), data))
+}
+
+/// Formats rows of tiles so that `parse` gives them back.
+pub fn write<'a>(rows: impl Iterator<Item = &'a [Option<Tile>]>) -> String {
+	let mut ret = String::new();
+	for row in rows {
+		for tile in row {
+			match tile {
+				Some(tile) => ret.push_str(tile.repr()),
+				None => ret.push(EMPTY),
+			}
+		}
+		ret.push('\n');
+	}
+	ret
+}
+
+#[cfg(test)]
+mod test {
+	use super::{parse, write};
+
+	#[test]
+	fn round_trip() {
+		let text = "0A.a\n^}.&\n";
+		assert_eq!(write(parse(text).unwrap().rows()), text);
+	}
+	#[test]
+	fn errors() {
+		assert!(parse("").is_err());
+		assert!(parse("0A\n0").is_err());
+		assert!(parse("0x").is_err());
+		assert!(parse("0A\n\nA0\n").is_err());
+		assert!(parse("\n0A\n").is_err());
+		assert_eq!(parse("0A\nA0\n\n\n").unwrap().size().y, 2);
+	}
+}
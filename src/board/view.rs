@@ -1,28 +1,50 @@
-use super::Board;
+use super::{Board, Zoom};
 use crate::ext::{direction::*, vec2::*};
+use crate::tile::Tile;
 use cursive::event::{Event, EventResult};
-use cursive::{theme::Effect, Printer, Rect, View};
+use cursive::theme::{ColorStyle, Effect, Style};
+use cursive::{Printer, Rect, View};
+use unicode_width::UnicodeWidthStr;
 
 impl View for Board {
 	fn draw(&self, printer: &Printer<'_, '_>) {
-		for (y, row) in self.rows().enumerate() {
+		let cell = self.cell_size();
+		// keep the space the board takes up, but do not let it be studied while the clock is stopped
+		if self.paused {
+			let text = "Paused";
+			let size = self.tiles.size() * cell;
+			printer.print((size.x.saturating_sub(text.len()) / 2, size.y / 2), text);
+			return;
+		}
+		let path_shown = matches!(self.shown_path, Some((shown_time, _)) if shown_time.elapsed().as_millis() < 400);
+		let tiles = match &self.shown_tiles {
+			Some(shown_tiles) if path_shown => shown_tiles,
+			_ => &self.tiles,
+		};
+		for (y, row) in tiles.rows().enumerate() {
 			for (x, tile) in row.iter().enumerate() {
 				let pos = Vec2::from((x, y));
-				let style = if self.confirmed_selection.map(|sel| sel == pos).unwrap_or(false) {
-					Effect::Reverse
+				let highlight = if self.confirmed_selection.map(|sel| sel == pos).unwrap_or(false) {
+					self.styles.selection
 				} else if self.tentative_selection.map(|(_, sel)| sel == pos).unwrap_or(false) {
 					// the if condition would return false if the tentative selection was None. This is admittedly a bit ugly but I couldn't think of a better way.
 					let blink_on = self.tentative_selection.unwrap().0.elapsed().subsec_millis() < 500;
 					if blink_on {
-						Effect::Reverse
+						self.styles.selection
 					} else {
-						Effect::Simple
+						Style::none()
 					}
+				} else if self.is_hinted(pos) {
+					self.styles.hint
 				} else {
-					Effect::Simple
+					Style::none()
 				};
-				printer.with_effect(style, move |printer| {
-					printer.print(pos, tile.map(|tile| tile.repr()).unwrap_or(" "));
+				let style = tile.map(|tile| suit_style(printer, tile)).unwrap_or_else(Style::none).combine(highlight);
+				let lines = self.cell_lines(*tile);
+				printer.with_style(style, move |printer| {
+					for (row, line) in lines.iter().enumerate() {
+						printer.print((pos.x * cell.x, pos.y * cell.y + row), line);
+					}
 				})
 			}
 		}
@@ -31,70 +53,132 @@ impl View for Board {
 			let shown_time = shown_time.elapsed().as_millis();
 			if shown_time < 400 {
 				let effect = if shown_time < 200 { Effect::Simple } else { Effect::Dim };
-				printer.with_effect(effect, move |printer| {
-					for lines in path.windows(2) {
-						let start = lines[0];
-						let end = lines[1];
-						if start.x == end.x {
-							// vertical line
-							let x = start.x;
-							let (start, end) = if start.y < end.y { (start.y, end.y) } else { (end.y, start.y) };
-							let (start, end) = (start + 1, end - 1);
-							for y in start..=end {
-								let pos = Vec2::from((x, y));
-								printer.print(pos, "\u{2502}");
-							}
-						} else if start.y == end.y {
-							// horizontal line
-							let y = start.y;
-							let (start, end) = if start.x < end.x { (start.x, end.x) } else { (end.x, start.x) };
-							let (start, end) = (start + 1, end - 1);
-							for x in start..=end {
-								let pos = Vec2::from((x, y));
-								printer.print(pos, "\u{2500}");
-							}
-						} else {
-							panic!("Path line is neither vertical nor horizontal ({:?} to {:?})", start, end);
-						}
-					}
-					for corners in path.windows(3) {
-						let corner_pos = corners[1];
-						let joint = Direction::joint_repr(Direction::from_positions(corner_pos, corners[0]), Direction::from_positions(corner_pos, corners[2]));
-						printer.print(corner_pos, joint);
-					}
-				});
+				printer.with_style(self.styles.path.combine(effect), move |printer| draw_path(printer, path, cell));
+			}
+		}
+		if let Some((ref shown_time, ref path)) = self.hint {
+			let shown_time = shown_time.elapsed().as_millis();
+			if shown_time < Self::HINT_DURATION {
+				let effect = if shown_time < Self::HINT_DURATION * 2 / 3 { Effect::Simple } else { Effect::Dim };
+				printer.with_style(self.styles.path.combine(effect), move |printer| draw_path(printer, path, cell));
 			}
 		}
 	}
 	fn needs_relayout(&self) -> bool {
-		false
+		self.needs_relayout
+	}
+	fn layout(&mut self, _size: Vec2) {
+		self.needs_relayout = false;
 	}
 	fn required_size(&mut self, _constraint: Vec2) -> Vec2 {
-		self.tiles.size()
+		self.tiles.size() * self.cell_size()
 	}
 	fn on_event(&mut self, event: Event) -> EventResult {
 		use cursive::event::{MouseButton, MouseEvent};
-		match event {
+		let result = match event {
 			Event::Mouse {
 				offset,
 				position,
 				event: MouseEvent::Release(MouseButton::Left),
 			} => {
-				if Rect::from_size(offset, self.tiles.size()).contains(position) {
-					self.on_click(position - offset);
-					EventResult::Consumed(None)
+				let cell = self.cell_size();
+				if !self.paused && Rect::from_size(offset, self.tiles.size() * cell).contains(position) {
+					self.on_click((position - offset) / cell)
 				} else {
 					EventResult::Ignored
 				}
 			}
-			Event::Key(key) => {
-				if self.on_key(key) {
-					EventResult::Consumed(None)
-				} else {
-					EventResult::Ignored
-				}
+			event => match self.keymap.action(&event) {
+				Some(action) => self.on_action(action),
+				None => EventResult::Ignored,
+			},
+		};
+		if result.is_consumed() {
+			let score = self.score();
+			result.and(EventResult::with_cb(move |siv| crate::game::show_score(siv, score)))
+		} else {
+			result
+		}
+	}
+}
+
+impl Board {
+	/// The number of columns and rows each position on the board takes up.
+	fn cell_size(&self) -> Vec2 {
+		let width = self.glyphs.width();
+		match self.zoom {
+			Zoom::Small => Vec2::new(width, 1),
+			Zoom::Medium => Vec2::new(width + 2, 2),
+			Zoom::Large => Vec2::new(width + 4, 3),
+		}
+	}
+	/// The text of each row of a cell, which is blank for empty cells.
+	fn cell_lines(&self, tile: Option<Tile>) -> Vec<String> {
+		let cell = self.cell_size();
+		let Some(tile) = tile else {
+			return vec![" ".repeat(cell.x); cell.y];
+		};
+		let glyph = self.glyphs.show(tile);
+		// pad narrow glyphs so the cell is filled and the highlight covers all of it
+		let face = format!("{}{}", glyph, " ".repeat(self.glyphs.width().saturating_sub(glyph.width())));
+		// only the boxed zooms have edges, which leave room for the sides
+		let edge = || "\u{2500}".repeat(cell.x - 2);
+		match self.zoom {
+			Zoom::Small => vec![face],
+			Zoom::Medium => vec![format!("\u{250c}{}\u{2510}", edge()), format!("\u{2502}{}\u{2502}", face)],
+			Zoom::Large => vec![format!("\u{250c}{}\u{2510}", edge()), format!("\u{2502} {} \u{2502}", face), format!("\u{2514}{}\u{2518}", edge())],
+		}
+	}
+}
+
+/// The colour of the tile's suit from the theme, or bold for honours if the theme has no colour for it.
+fn suit_style(printer: &Printer<'_, '_>, tile: Tile) -> Style {
+	let suit = tile.suit();
+	match printer.theme.palette.custom(suit.palette_key()) {
+		Some(&color) => ColorStyle::front(color).into(),
+		None if suit.is_honor() => Effect::Bold.into(),
+		None => Style::none(),
+	}
+}
+
+/// Draws the lines and corners of a path between two tiles, leaving the tiles themselves untouched.
+///
+/// Each position takes up `cell` columns and rows. Lines run through the middle of the cells, and stop at the edge of the cells of the two tiles.
+fn draw_path(printer: &Printer<'_, '_>, path: &[Vec2], cell: Vec2) {
+	let is_tile = |pos: Vec2| path.first() == Some(&pos) || path.last() == Some(&pos);
+	let middle = |pos: Vec2| pos * cell + cell / 2;
+	// the screen coordinates along one axis strictly between two positions, leaving out the whole cell of a tile
+	let between = |low: Vec2, high: Vec2, axis: fn(Vec2) -> usize| {
+		let start = if is_tile(low) { axis((low + (1, 1)) * cell) } else { axis(middle(low)) + 1 };
+		let end = if is_tile(high) { axis(high * cell) } else { axis(middle(high)) };
+		start..end
+	};
+	for lines in path.windows(2) {
+		let start = lines[0];
+		let end = lines[1];
+		if start.x == end.x {
+			// vertical line
+			let x = middle(start).x;
+			let (top, bottom) = if start.y < end.y { (start, end) } else { (end, start) };
+			for y in between(top, bottom, |pos| pos.y) {
+				let pos = Vec2::from((x, y));
+				printer.print(pos, "\u{2502}");
 			}
-			_ => EventResult::Ignored,
+		} else if start.y == end.y {
+			// horizontal line
+			let y = middle(start).y;
+			let (left, right) = if start.x < end.x { (start, end) } else { (end, start) };
+			for x in between(left, right, |pos| pos.x) {
+				let pos = Vec2::from((x, y));
+				printer.print(pos, "\u{2500}");
+			}
+		} else {
+			panic!("Path line is neither vertical nor horizontal ({:?} to {:?})", start, end);
 		}
 	}
+	for corners in path.windows(3) {
+		let corner_pos = corners[1];
+		let joint = Direction::joint_repr(Direction::from_positions(corner_pos, corners[0]), Direction::from_positions(corner_pos, corners[2]));
+		printer.print(middle(corner_pos), joint);
+	}
 }
@@ -0,0 +1,72 @@
+use crate::board::{Board, Deal, Rules, Zoom};
+use crate::ext::vec2::*;
+use crate::tile::{Glyphs, TileSet};
+use anyhow::Context;
+use clap::Parser;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Args {
+	/// Board size as WIDTHxHEIGHT, not counting the empty border.
+	#[arg(long, value_parser = parse_size, default_value = "18x8")]
+	pub size: Vec2,
+	/// Seed for the first deal, to play the same deal as someone else. A random seed is used if this is not given.
+	#[arg(long)]
+	pub seed: Option<u64>,
+	/// How the tiles are dealt.
+	#[arg(long, value_enum, default_value_t)]
+	pub deal: Deal,
+	/// Which tiles the board is dealt from.
+	#[arg(long, value_enum, default_value_t)]
+	pub tile_set: TileSet,
+	/// Let the tiles above a removed pair fall down to fill the gap.
+	#[arg(long)]
+	pub gravity: bool,
+	/// How many times the path between a pair may change direction. Standard rules allow 2.
+	#[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u8).range(0..=3))]
+	pub max_turns: u8,
+	/// Keep paths inside the playfield instead of letting them run around the outside of the board.
+	#[arg(long)]
+	pub no_outer_corridor: bool,
+	/// How tiles are drawn. Unicode mahjong tiles need a font that has them.
+	#[arg(long, value_enum, default_value_t)]
+	pub glyphs: Glyphs,
+	/// How large tiles are drawn. This can also be changed while playing with + and -.
+	#[arg(long, value_enum, default_value_t)]
+	pub zoom: Zoom,
+	/// Draw tiles without colours. This is also the default when the NO_COLOR environment variable is set or the terminal has no colours.
+	#[arg(long)]
+	pub monochrome: bool,
+	/// The theme: default, dark, light, high-contrast, the name of a file in the user theme directory, or the path of a TOML or JSON theme file. Press t while playing to switch themes.
+	#[arg(long, value_name = "THEME", default_value = crate::theme::DEFAULT)]
+	pub theme: String,
+	/// The keys: default, vim, wasd, or the path of a TOML or JSON keymap file. Without this, keys.toml in the configuration directory is used if it exists. Press ? while playing to list the keys.
+	#[arg(long, value_name = "KEYMAP")]
+	pub keys: Option<String>,
+	/// Start from a board file instead of dealing a new board.
+	#[arg(long, value_name = "BOARD_FILE", conflicts_with_all = ["size", "seed", "deal", "tile_set"])]
+	pub load: Option<PathBuf>,
+	/// Print a sequence of moves that clears a board file, then exit. Fails if the board cannot be cleared, or with "gave up" if the solver cannot decide within its limit of a few hundred positions (about a quarter of a second).
+	#[arg(long, value_name = "BOARD_FILE", conflicts_with_all = ["size", "seed", "deal", "tile_set", "gravity", "load"])]
+	pub solve: Option<PathBuf>,
+}
+
+impl Args {
+	pub fn rules(&self) -> Rules {
+		Rules {
+			gravity: self.gravity,
+			max_turns: self.max_turns.into(),
+			outer_corridor: !self.no_outer_corridor,
+		}
+	}
+}
+
+fn parse_size(size: &str) -> anyhow::Result<Vec2> {
+	let (width, height) = size.split_once('x').context("Expected a size like 18x8")?;
+	let width = width.parse().with_context(|| format!("Invalid width {:?}", width))?;
+	let height = height.parse().with_context(|| format!("Invalid height {:?}", height))?;
+	let size = Vec2::new(width, height);
+	Board::check_size(size)?;
+	Ok(size)
+}
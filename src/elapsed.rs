@@ -1,23 +1,100 @@
 use crate::ext::vec2::*;
 use cursive::{Printer, View};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-pub struct Elapsed(Instant);
+pub struct Elapsed {
+	// the time counted up to when the clock was last started, including penalties, e.g. for reshuffling
+	accumulated: Duration,
+	// when the clock was last started, or None while the game is paused
+	running_since: Option<Instant>,
+	// set once the game is over so the displayed time stops advancing
+	stopped: Option<Duration>,
+}
 
 impl Elapsed {
 	pub fn new() -> Self {
-		Self(Instant::now())
+		Self::resumed(Duration::ZERO)
+	}
+	/// Continues counting from a previously saved time.
+	pub fn resumed(elapsed: Duration) -> Self {
+		Self {
+			accumulated: elapsed,
+			running_since: Some(Instant::now()),
+			stopped: None,
+		}
+	}
+	pub fn elapsed(&self) -> Duration {
+		self.stopped.unwrap_or_else(|| self.accumulated + self.running_since.map_or(Duration::ZERO, |since| since.elapsed()))
+	}
+	pub fn add_penalty(&mut self, penalty: Duration) {
+		self.accumulated += penalty;
+	}
+	fn text(&self) -> String {
+		let state = if self.is_paused() { "paused" } else { "elapsed" };
+		format!("{} {}", format(self.elapsed()), state)
+	}
+	/// Returns true once the game is over.
+	pub fn is_stopped(&self) -> bool {
+		self.stopped.is_some()
+	}
+	/// Returns true while the clock is paused during a game.
+	pub fn is_paused(&self) -> bool {
+		self.running_since.is_none() && !self.is_stopped()
+	}
+	/// Stops counting until `resume` is called.
+	pub fn pause(&mut self) {
+		if let Some(since) = self.running_since.take() {
+			self.accumulated += since.elapsed();
+		}
+	}
+	pub fn resume(&mut self) {
+		self.running_since.get_or_insert_with(Instant::now);
 	}
+	/// Freezes the timer and returns the final elapsed time.
+	pub fn stop(&mut self) -> Duration {
+		let elapsed = self.elapsed();
+		*self.stopped.get_or_insert(elapsed)
+	}
+}
+
+/// Formats a duration as `minutes:seconds`.
+pub fn format(duration: Duration) -> String {
+	let elapsed = duration.as_secs();
+	let (minutes, seconds) = (elapsed / 60, elapsed % 60);
+	format!("{}:{:02}", minutes, seconds)
 }
 
 impl View for Elapsed {
 	fn draw(&self, printer: &Printer<'_, '_>) {
-		let elapsed = self.0.elapsed().as_secs();
-		let (minutes, seconds) = (elapsed / 60, elapsed % 60);
-		let text = format!("{}:{:02} elapsed", minutes, seconds);
-		printer.print(cursive::Vec2::new(0, 0), &text)
+		printer.print(cursive::Vec2::new(0, 0), &self.text())
+	}
+	fn required_size(&mut self, _constraint: Vec2) -> Vec2 {
+		Vec2::new(self.text().len(), 1)
 	}
-	fn required_size(&mut self, constraint: Vec2) -> Vec2 {
-		constraint.with_y(1)
+}
+
+#[cfg(test)]
+mod test {
+	use super::Elapsed;
+	use std::time::Duration;
+
+	#[test]
+	fn pause_and_resume() {
+		let mut elapsed = Elapsed::resumed(Duration::from_secs(60));
+		elapsed.pause();
+		assert!(elapsed.is_paused());
+		let paused = elapsed.elapsed();
+		std::thread::sleep(Duration::from_millis(20));
+		assert_eq!(elapsed.elapsed(), paused);
+		elapsed.add_penalty(Duration::from_secs(30));
+		assert_eq!(elapsed.elapsed(), paused + Duration::from_secs(30));
+
+		elapsed.resume();
+		std::thread::sleep(Duration::from_millis(20));
+		assert!(elapsed.elapsed() >= paused + Duration::from_millis(30_020));
+		let stopped = elapsed.stop();
+		elapsed.pause();
+		assert!(!elapsed.is_paused());
+		assert_eq!(elapsed.elapsed(), stopped);
 	}
 }
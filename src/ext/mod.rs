@@ -1,5 +1,2 @@
 pub mod direction;
 pub mod vec2;
-
-pub use direction::{Direction, DirectionExt};
-pub use vec2::{Vec2, Vec2Ext};
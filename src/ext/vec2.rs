@@ -6,12 +6,8 @@ pub trait Vec2Ext {
 	fn width(self) -> usize;
 	fn height(self) -> usize;
 	fn contains(self, inner: Vec2) -> bool;
-	fn max_within(self) -> Vec2;
-	fn random_within(self) -> Vec2;
 	fn with_x(self, new_x: usize) -> Vec2;
 	fn with_y(self, new_y: usize) -> Vec2;
-	fn map_x(self, f: impl FnOnce(usize) -> usize) -> Vec2;
-	fn map_y(self, f: impl FnOnce(usize) -> usize) -> Vec2;
 	fn move_wrapping(&mut self, movement: XY<isize>, within: Vec2);
 }
 
@@ -32,17 +28,6 @@ impl Vec2Ext for Vec2 {
 		inner.x < self.width() && inner.y < self.height()
 	}
 	#[inline]
-	fn max_within(self) -> Self {
-		Vec2::from((self.x - 1, self.y - 1))
-	}
-	fn random_within(self) -> Self {
-		use rand::Rng;
-		let mut rng = rand::thread_rng();
-		let x = rng.gen_range(0..self.x);
-		let y = rng.gen_range(0..self.y);
-		Self::from((x, y))
-	}
-	#[inline]
 	fn with_x(self, new_x: usize) -> Vec2 {
 		Vec2::from((new_x, self.y))
 	}
@@ -50,12 +35,6 @@ impl Vec2Ext for Vec2 {
 	fn with_y(self, new_y: usize) -> Vec2 {
 		Vec2::from((self.x, new_y))
 	}
-	fn map_x(self, f: impl FnOnce(usize) -> usize) -> Vec2 {
-		self.with_x(f(self.x))
-	}
-	fn map_y(self, f: impl FnOnce(usize) -> usize) -> Vec2 {
-		self.with_y(f(self.y))
-	}
 	fn move_wrapping(&mut self, movement: XY<isize>, within: Vec2) {
 		macro_rules! do_it {
 			($field:ident) => {
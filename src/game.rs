@@ -0,0 +1,450 @@
+use crate::board::{Board, Deal, Rules, Solution};
+use crate::elapsed::{self, Elapsed};
+use crate::ext::vec2::*;
+use crate::matrix::Matrix;
+use crate::records::{self, Outcome, Record, Summary};
+use crate::save::{self, SavedGame};
+use crate::theme;
+use crate::tile::{Tile, TileSet};
+use anyhow::Context;
+use cursive::traits::Nameable;
+use cursive::views::{Checkbox, Dialog, LinearLayout, SelectView, TextView};
+use cursive::Cursive;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Time added to the clock when the player asks for a reshuffle.
+const RESHUFFLE_PENALTY: Duration = Duration::from_secs(30);
+
+/// Options used when starting a new game, stored as the `Cursive` user data.
+#[derive(Clone)]
+pub struct Settings {
+	pub size: Vec2,
+	pub deal: Deal,
+	pub tile_set: TileSet,
+	pub rules: Rules,
+	/// The name or path of the theme, as understood by `theme::load`.
+	pub theme: String,
+	/// Draw everything in the terminal's default colours.
+	pub monochrome: bool,
+}
+
+impl Default for Settings {
+	fn default() -> Self {
+		Self {
+			size: Board::DEFAULT_SIZE,
+			deal: Deal::default(),
+			tile_set: TileSet::default(),
+			rules: Rules::default(),
+			theme: theme::DEFAULT.to_owned(),
+			monochrome: false,
+		}
+	}
+}
+
+/// Starts a new game with a fresh random seed.
+pub fn new_game(siv: &mut Cursive) {
+	let settings = siv.with_user_data(|settings: &mut Settings| settings.clone()).unwrap_or_default();
+	let seed = rand::random();
+	let board = match Board::new(settings.size, settings.deal, settings.tile_set, settings.rules, seed) {
+		Ok(board) => board,
+		Err(error) => {
+			// the current game carries on
+			siv.add_layer(Dialog::info(format!("{:#}", error)).title("Could not deal"));
+			return;
+		}
+	};
+	let in_progress = siv.call_on_name("elapsed", |elapsed: &mut Elapsed| !elapsed.is_stopped()).expect("Elapsed view missing");
+	let moves = siv.call_on_name("board", |board: &mut Board| board.stats().moves).expect("Board view missing");
+	// a deal that was replaced without making a move does not count as a game
+	if in_progress && moves > 0 {
+		if let Err(error) = record(siv, Outcome::Abandoned) {
+			siv.add_layer(Dialog::info(format!("Could not record the game: {:#}", error)).title("Error"));
+		}
+	}
+	replace_board(siv, board);
+	siv.call_on_name("elapsed", |current_elapsed| *current_elapsed = Elapsed::new());
+	siv.call_on_name("status", |label: &mut TextView| label.set_content(seed_label(seed)));
+	siv.call_on_name("rules", |label: &mut TextView| label.set_content(rules_label(settings.rules)));
+	show_score(siv, 0);
+}
+
+/// Pauses a game in progress, or resumes a paused one.
+pub fn toggle_pause(siv: &mut Cursive) {
+	let paused = siv.call_on_name("board", |board: &mut Board| board.is_paused()).expect("Board view missing");
+	set_paused(siv, !paused);
+}
+
+fn set_paused(siv: &mut Cursive, paused: bool) {
+	let over = siv.call_on_name("elapsed", |elapsed: &mut Elapsed| elapsed.is_stopped()).expect("Elapsed view missing");
+	// there is no clock to stop once the game is over
+	if over && paused {
+		return;
+	}
+	siv.call_on_name("elapsed", |elapsed: &mut Elapsed| if paused { elapsed.pause() } else { elapsed.resume() });
+	siv.call_on_name("board", |board: &mut Board| board.set_paused(paused));
+}
+
+/// Swaps in a new board, drawn and controlled the same way as the one it replaces.
+fn replace_board(siv: &mut Cursive, mut board: Board) {
+	siv.call_on_name("board", |current_board: &mut Board| {
+		board.copy_preferences(current_board);
+		*current_board = board;
+	});
+}
+
+/// Applies the theme from the settings to the interface and the board.
+pub fn apply_theme(siv: &mut Cursive) -> anyhow::Result<()> {
+	let settings = siv.with_user_data(|settings: &mut Settings| settings.clone()).unwrap_or_default();
+	let loaded = theme::load(&settings.theme, !settings.monochrome)?;
+	siv.set_theme(loaded.theme);
+	siv.call_on_name("board", |board: &mut Board| board.set_styles(loaded.board));
+	Ok(())
+}
+
+/// Switches to the next bundled or user theme.
+pub fn next_theme(siv: &mut Cursive) {
+	let themes = theme::available();
+	let current = siv.with_user_data(|settings: &mut Settings| settings.theme.clone()).unwrap_or_default();
+	// a theme given as a path is not in the list, so cycling starts over from the first one
+	let next = themes.iter().position(|name| *name == current).map_or(0, |index| (index + 1) % themes.len());
+	siv.with_user_data(|settings: &mut Settings| settings.theme = themes[next].clone());
+	if let Err(error) = apply_theme(siv) {
+		siv.add_layer(Dialog::info(format!("{:#}", error)).title("Could not load theme"));
+	}
+}
+
+/// Describes the rules that differ between games, for the status line.
+pub fn rules_label(rules: Rules) -> String {
+	let turns = if rules.max_turns == 1 { "turn" } else { "turns" };
+	let gravity = if rules.gravity { ", gravity" } else { "" };
+	let corridor = if rules.outer_corridor { "" } else { ", no corridor" };
+	format!("  {} {}{}{}", rules.max_turns, turns, gravity, corridor)
+}
+
+pub fn show_score(siv: &mut Cursive, score: i64) {
+	siv.call_on_name("score", |label: &mut TextView| label.set_content(score_label(score)));
+}
+
+pub fn score_label(score: i64) -> String {
+	format!("  score {}", score)
+}
+
+/// The text shown next to the clock, so the seed can be shared to replay the same deal.
+pub fn seed_label(seed: u64) -> String {
+	format!("  seed {}", seed)
+}
+
+/// Asks which kind of deal and rules to use, then starts a new game with them.
+pub fn new_game_dialog(siv: &mut Cursive) {
+	let current = siv.with_user_data(|settings: &mut Settings| settings.clone()).unwrap_or_default();
+	let mut deals = SelectView::new().item("Random deal", Deal::Random).item("Solvable deal", Deal::Solvable).on_select(|siv, &deal| {
+		let gravity = siv.call_on_name("gravity", |gravity: &mut Checkbox| gravity.is_checked()).expect("Gravity view missing");
+		show_deal_warning(siv, deal, gravity);
+	});
+	deals.set_selection(if current.deal == Deal::Solvable { 1 } else { 0 });
+	let mut tile_sets = SelectView::new().popup().item("Classic tiles", TileSet::Classic).item("Flowers and seasons", TileSet::Traditional);
+	tile_sets.set_selection(if current.tile_set == TileSet::Traditional { 1 } else { 0 });
+	let mut turns = SelectView::new().popup().item("No turns", 0).item("1 turn (hard)", 1).item("2 turns", 2).item("3 turns (easy)", 3);
+	turns.set_selection(current.rules.max_turns);
+	let gravity_checkbox = Checkbox::new().with_checked(current.rules.gravity).on_change(|siv, gravity| {
+		let deal = siv.call_on_name("deal", |deals: &mut SelectView<Deal>| *deals.selection().expect("A deal is always selected")).expect("Deal view missing");
+		show_deal_warning(siv, deal, gravity);
+	});
+	let gravity = LinearLayout::horizontal().child(gravity_checkbox.with_name("gravity")).child(TextView::new(" Gravity"));
+	let corridor = LinearLayout::horizontal()
+		.child(Checkbox::new().with_checked(current.rules.outer_corridor).with_name("corridor"))
+		.child(TextView::new(" Paths around the outside"));
+	siv.add_layer(
+		Dialog::around(
+			LinearLayout::vertical()
+				.child(deals.with_name("deal"))
+				.child(tile_sets.with_name("tile set"))
+				.child(turns.with_name("turns"))
+				.child(gravity)
+				.child(corridor)
+				.child(TextView::new(deal_warning(current.deal, current.rules.gravity)).with_name("deal warning")),
+		)
+		.title("New game")
+		.button("Start", |siv| {
+			let deal = siv.call_on_name("deal", |deals: &mut SelectView<Deal>| *deals.selection().expect("A deal is always selected")).expect("Deal view missing");
+			let tile_set = siv
+				.call_on_name("tile set", |tile_sets: &mut SelectView<TileSet>| *tile_sets.selection().expect("A tile set is always selected"))
+				.expect("Tile set view missing");
+			let max_turns = siv.call_on_name("turns", |turns: &mut SelectView<usize>| *turns.selection().expect("A turn limit is always selected")).expect("Turns view missing");
+			let gravity = siv.call_on_name("gravity", |gravity: &mut Checkbox| gravity.is_checked()).expect("Gravity view missing");
+			let outer_corridor = siv.call_on_name("corridor", |corridor: &mut Checkbox| corridor.is_checked()).expect("Corridor view missing");
+			siv.pop_layer();
+			siv.with_user_data(|settings: &mut Settings| {
+				settings.deal = deal;
+				settings.tile_set = tile_set;
+				settings.rules = Rules { gravity, max_turns, outer_corridor };
+			});
+			new_game(siv);
+		})
+		.dismiss_button("Cancel"),
+	);
+}
+
+/// Warns that a deal is only guaranteed to be solvable without gravity, since falling tiles can block the solution it was built with.
+fn deal_warning(deal: Deal, gravity: bool) -> &'static str {
+	if deal == Deal::Solvable && gravity {
+		"Gravity can make a solvable deal unsolvable."
+	} else {
+		""
+	}
+}
+
+fn show_deal_warning(siv: &mut Cursive, deal: Deal, gravity: bool) {
+	siv.call_on_name("deal warning", |warning: &mut TextView| warning.set_content(deal_warning(deal, gravity)));
+}
+
+/// Stops the clock, records the result and shows the end-of-game summary.
+fn game_over(siv: &mut Cursive, outcome: Outcome) {
+	siv.call_on_name("elapsed", Elapsed::stop).expect("Elapsed view missing");
+	let title = match outcome {
+		Outcome::Won => "Board cleared!",
+		Outcome::Lost | Outcome::Abandoned => "Game over",
+	};
+	let mut summary = match record(siv, outcome) {
+		Ok(record) => format!(
+			"Score: {}\nTime: {}\nMoves: {}\nHints used: {}\nReshuffles: {}",
+			record.score,
+			elapsed::format(record.time),
+			record.stats.moves,
+			record.stats.hints,
+			record.stats.shuffles
+		),
+		Err(error) => format!("Could not record the game: {:#}", error),
+	};
+	if let Some(best) = best_time(siv) {
+		summary += &format!("\nBest time: {}", elapsed::format(best));
+	}
+	siv.add_layer(
+		Dialog::text(summary)
+			.title(title)
+			.button("New game", |siv| {
+				siv.pop_layer();
+				new_game(siv);
+			})
+			.button("Quit", quit),
+	);
+}
+
+pub fn won(siv: &mut Cursive) {
+	game_over(siv, Outcome::Won);
+}
+
+/// Adds the current game to the records and returns what was recorded.
+fn record(siv: &mut Cursive, outcome: Outcome) -> anyhow::Result<Record> {
+	let time = siv.call_on_name("elapsed", |elapsed: &mut Elapsed| elapsed.elapsed()).expect("Elapsed view missing");
+	let record = siv.call_on_name("board", |board: &mut Board| game_record(board, time, outcome)).expect("Board view missing");
+	records::append(&record)?;
+	Ok(record)
+}
+
+fn game_record(board: &Board, time: Duration, outcome: Outcome) -> Record {
+	Record {
+		size: (board.size().x, board.size().y),
+		seed: board.seed(),
+		time,
+		stats: board.stats(),
+		score: board.score(),
+		outcome,
+	}
+}
+
+/// Records the saved game as abandoned, as it is thrown away when the player starts a new game instead of resuming it.
+fn abandon_saved(saved: SavedGame) -> anyhow::Result<()> {
+	let board = Board::restore(saved.board)?;
+	// like in `new_game`, a deal that was left without making a move does not count as a game
+	if board.stats().moves > 0 {
+		records::append(&game_record(&board, saved.elapsed, Outcome::Abandoned))?;
+	}
+	Ok(())
+}
+
+/// The best winning time on a board of the current size, if there is one.
+fn best_time(siv: &mut Cursive) -> Option<Duration> {
+	let size = siv.call_on_name("board", |board: &mut Board| board.size()).expect("Board view missing");
+	let records = records::read().ok()?;
+	Summary::new(&records).by_size.get(&(size.x, size.y)).map(|best| best.time)
+}
+
+/// Shows win rate, streaks and the best time for each board size.
+pub fn statistics(siv: &mut Cursive) {
+	let text = match records::read() {
+		Ok(records) => {
+			let summary = Summary::new(&records);
+			let mut text = format!(
+				"Games played: {}\nWon: {} ({}%)\nCurrent streak: {}\nLongest streak: {}\n",
+				summary.played,
+				summary.won,
+				summary.win_rate(),
+				summary.current_streak,
+				summary.longest_streak
+			);
+			if !summary.by_size.is_empty() {
+				text += "\nSize    Wins  Best time  Best score\n";
+			}
+			for ((width, height), best) in summary.by_size {
+				text += &format!("{:<7} {:>4}  {:>9}  {:>10}\n", format!("{}x{}", width, height), best.wins, elapsed::format(best.time), best.score);
+			}
+			text
+		}
+		Err(error) => format!("{:#}", error),
+	};
+	siv.add_layer(Dialog::info(text).title("Statistics"));
+}
+
+/// Runs the solver on a background thread, since hard positions can take a moment to decide, and reports the result in a dialog.
+///
+/// The dialog says the solver is busy until the answer comes in, and only one position is checked at a time.
+pub fn check_winnable(siv: &mut Cursive, tiles: Matrix<Option<Tile>>, rules: Rules) {
+	if rules.gravity {
+		siv.add_layer(Dialog::info("The solver does not support gravity.").title("Solver"));
+		return;
+	}
+	if siv.find_name::<Dialog>("solver").is_some() {
+		return;
+	}
+	// an answer that arrives after the check was cancelled belongs to a check nobody is waiting for
+	let check = SOLVER_CHECKS.fetch_add(1, Ordering::Relaxed) + 1;
+	siv.add_layer(Dialog::text("Checking whether this position can be cleared...").title("Solver").dismiss_button("Cancel").with_name("solver"));
+	let sink = siv.cb_sink().clone();
+	std::thread::spawn(move || {
+		let solution = tiles.solve(rules.max_turns);
+		// the only error is that cursive has already quit, in which case nobody is waiting for the answer
+		let _ = sink.send(Box::new(move |siv| {
+			if SOLVER_CHECKS.load(Ordering::Relaxed) == check {
+				winnable_dialog(siv, solution);
+			}
+		}));
+	});
+}
+
+/// Counts the solver checks that were started, so only the answer to the latest one is shown.
+static SOLVER_CHECKS: AtomicUsize = AtomicUsize::new(0);
+
+fn winnable_dialog(siv: &mut Cursive, solution: Solution) {
+	let text = match solution {
+		Solution::Found(_) => "This position can still be cleared.",
+		Solution::Unsolvable => "This position can no longer be cleared.",
+		Solution::Unknown => "The solver gave up, as this position is too hard to decide quickly.",
+	};
+	siv.call_on_name("solver", |dialog: &mut Dialog| {
+		dialog.set_content(TextView::new(text));
+		dialog.clear_buttons();
+		dialog.add_button("Ok", |siv| {
+			siv.pop_layer();
+		});
+	});
+}
+
+/// Writes the current position to a file in the text format, e.g. to attach it to a bug report, and shows it.
+pub fn dump(siv: &mut Cursive, text: &str) {
+	let written = save::data_dir().and_then(|dir| {
+		std::fs::create_dir_all(&dir).context("Could not create the data directory")?;
+		let path = dir.join("board.txt");
+		std::fs::write(&path, text).with_context(|| format!("Could not write {:?}", path))?;
+		Ok(path)
+	});
+	let message = match written {
+		Ok(path) => format!("Saved to {}\n\n{}", path.display(), text),
+		Err(error) => format!("{:#}\n\n{}", error, text),
+	};
+	siv.add_layer(Dialog::info(message).title("Board"));
+}
+
+/// Lists the keys and what they do.
+pub fn help(siv: &mut Cursive, text: &str) {
+	siv.add_layer(Dialog::info(text).title("Keys"));
+}
+
+/// Offers to reshuffle the remaining tiles when no pair can be connected.
+pub fn stuck(siv: &mut Cursive) {
+	siv.add_layer(
+		Dialog::text("There are no moves left.")
+			.title("Stuck")
+			.button(format!("Reshuffle (+{}s)", RESHUFFLE_PENALTY.as_secs()), |siv| {
+				siv.pop_layer();
+				siv.call_on_name("elapsed", |elapsed: &mut Elapsed| elapsed.add_penalty(RESHUFFLE_PENALTY));
+				let (score, still_stuck) = siv
+					.call_on_name("board", |board: &mut Board| {
+						board.reshuffle();
+						(board.score(), board.is_stuck())
+					})
+					.expect("Board view missing");
+				show_score(siv, score);
+				if still_stuck {
+					stuck(siv);
+				}
+			})
+			.button("Give up", |siv| {
+				siv.pop_layer();
+				game_over(siv, Outcome::Lost);
+			}),
+	);
+}
+
+/// Saves the game in progress (or discards the save once the game is over) and quits.
+pub fn quit(siv: &mut Cursive) {
+	match save(siv) {
+		Ok(()) => siv.quit(),
+		Err(error) => siv.add_layer(Dialog::text(format!("Could not save the game: {:#}", error)).title("Error").button("Quit anyway", Cursive::quit).dismiss_button("Cancel")),
+	}
+}
+
+/// Writes the game in progress to disk, or removes the saved game if this one is over.
+pub fn save(siv: &mut Cursive) -> anyhow::Result<()> {
+	let elapsed = siv.call_on_name("elapsed", |elapsed: &mut Elapsed| (!elapsed.is_stopped()).then(|| elapsed.elapsed())).expect("Elapsed view missing");
+	match elapsed {
+		Some(elapsed) => {
+			let board = siv.call_on_name("board", |board: &mut Board| board.save()).expect("Board view missing");
+			save::write(&SavedGame { board, elapsed })
+		}
+		None => save::remove(),
+	}
+}
+
+/// Asks whether to continue the saved game or keep the new one.
+pub fn offer_resume(siv: &mut Cursive, saved: SavedGame) {
+	let abandoned = saved.clone();
+	siv.add_layer(
+		Dialog::text("Resume the game from last time?")
+			.title("Saved game")
+			.button("Resume", move |siv| {
+				siv.pop_layer();
+				resume(siv, saved.clone());
+			})
+			.button("New game", move |siv| {
+				siv.pop_layer();
+				if let Err(error) = abandon_saved(abandoned.clone()) {
+					siv.add_layer(Dialog::info(format!("Could not record the saved game: {:#}", error)).title("Error"));
+				}
+				new_game(siv);
+			}),
+	);
+}
+
+fn resume(siv: &mut Cursive, saved: SavedGame) {
+	let board = match Board::restore(saved.board) {
+		Ok(board) => board,
+		Err(error) => {
+			siv.add_layer(Dialog::info(format!("Could not resume the saved game: {:#}", error)).title("Error"));
+			return;
+		}
+	};
+	let seed = board.seed();
+	let rules = board.rules();
+	let score = board.score();
+	let is_stuck = board.is_stuck();
+	replace_board(siv, board);
+	siv.call_on_name("elapsed", |current_elapsed| *current_elapsed = Elapsed::resumed(saved.elapsed));
+	siv.call_on_name("status", |label: &mut TextView| label.set_content(seed_label(seed)));
+	siv.call_on_name("rules", |label: &mut TextView| label.set_content(rules_label(rules)));
+	show_score(siv, score);
+	if is_stuck {
+		stuck(siv);
+	}
+}
@@ -0,0 +1,418 @@
+//! Which keys trigger which actions, from a built-in preset or a TOML or JSON file.
+//!
+//! A keymap file starts from a preset and rebinds some of its actions, with every entry optional:
+//!
+//! ```toml
+//! preset = "vim" # or "default" or "wasd"
+//!
+//! [keys]
+//! # each action replaces all of its keys from the preset
+//! hint = ["x"]
+//! quit = ["q", "ctrl-c"]
+//! ```
+//!
+//! Keys are single characters, `space`, the names of special keys such as `enter`, `left` or `f1`, and those with a `ctrl-`, `alt-` or `shift-` prefix.
+
+use anyhow::{bail, Context};
+use cursive::event::{Event, Key};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Something the player can do with a key, in the order they are listed in the help.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Action {
+	Left,
+	Right,
+	Up,
+	Down,
+	JumpLeft,
+	JumpRight,
+	JumpUp,
+	JumpDown,
+	Select,
+	Cancel,
+	Hint,
+	Undo,
+	Redo,
+	ZoomIn,
+	ZoomOut,
+	Pause,
+	Restart,
+	NewGame,
+	Statistics,
+	Theme,
+	Dump,
+	CheckWinnable,
+	Help,
+	Quit,
+}
+
+impl Action {
+	const ALL: [Self; 24] = [
+		Self::Left,
+		Self::Right,
+		Self::Up,
+		Self::Down,
+		Self::JumpLeft,
+		Self::JumpRight,
+		Self::JumpUp,
+		Self::JumpDown,
+		Self::Select,
+		Self::Cancel,
+		Self::Hint,
+		Self::Undo,
+		Self::Redo,
+		Self::ZoomIn,
+		Self::ZoomOut,
+		Self::Pause,
+		Self::Restart,
+		Self::NewGame,
+		Self::Statistics,
+		Self::Theme,
+		Self::Dump,
+		Self::CheckWinnable,
+		Self::Help,
+		Self::Quit,
+	];
+	/// The name of the action in keymap files.
+	fn name(self) -> &'static str {
+		match self {
+			Self::Left => "left",
+			Self::Right => "right",
+			Self::Up => "up",
+			Self::Down => "down",
+			Self::JumpLeft => "jump-left",
+			Self::JumpRight => "jump-right",
+			Self::JumpUp => "jump-up",
+			Self::JumpDown => "jump-down",
+			Self::Select => "select",
+			Self::Cancel => "cancel",
+			Self::Hint => "hint",
+			Self::Undo => "undo",
+			Self::Redo => "redo",
+			Self::ZoomIn => "zoom-in",
+			Self::ZoomOut => "zoom-out",
+			Self::Pause => "pause",
+			Self::Restart => "restart",
+			Self::NewGame => "new-game",
+			Self::Statistics => "statistics",
+			Self::Theme => "theme",
+			Self::Dump => "dump",
+			Self::CheckWinnable => "check-winnable",
+			Self::Help => "help",
+			Self::Quit => "quit",
+		}
+	}
+	fn description(self) -> &'static str {
+		match self {
+			Self::Left => "Move left",
+			Self::Right => "Move right",
+			Self::Up => "Move up",
+			Self::Down => "Move down",
+			Self::JumpLeft => "Jump to the next tile left",
+			Self::JumpRight => "Jump to the next tile right",
+			Self::JumpUp => "Jump to the next tile up",
+			Self::JumpDown => "Jump to the next tile down",
+			Self::Select => "Select the tile",
+			Self::Cancel => "Cancel the selection",
+			Self::Hint => "Show a hint",
+			Self::Undo => "Undo",
+			Self::Redo => "Redo",
+			Self::ZoomIn => "Zoom in",
+			Self::ZoomOut => "Zoom out",
+			Self::Pause => "Pause or resume",
+			Self::Restart => "Start a new game",
+			Self::NewGame => "Start a new game with other rules",
+			Self::Statistics => "Statistics",
+			Self::Theme => "Switch theme",
+			Self::Dump => "Show the board as text",
+			Self::CheckWinnable => "Check if the board can be cleared",
+			Self::Help => "Show the keys",
+			Self::Quit => "Save and quit",
+		}
+	}
+}
+
+/// The names of the built-in presets. The first one is the default.
+pub const PRESETS: [&str; 3] = ["default", "vim", "wasd"];
+
+const DEFAULT_KEYS: [(Action, &[&str]); 24] = [
+	(Action::Left, &["left"]),
+	(Action::Right, &["right"]),
+	(Action::Up, &["up"]),
+	(Action::Down, &["down"]),
+	(Action::JumpLeft, &["shift-left"]),
+	(Action::JumpRight, &["shift-right"]),
+	(Action::JumpUp, &["shift-up"]),
+	(Action::JumpDown, &["shift-down"]),
+	(Action::Select, &["enter"]),
+	(Action::Cancel, &["backspace"]),
+	(Action::Hint, &["h"]),
+	(Action::Undo, &["u"]),
+	(Action::Redo, &["U"]),
+	(Action::ZoomIn, &["+", "="]),
+	(Action::ZoomOut, &["-"]),
+	(Action::Pause, &["p", "pause"]),
+	(Action::Restart, &["r"]),
+	(Action::NewGame, &["n"]),
+	(Action::Statistics, &["s"]),
+	(Action::Theme, &["t"]),
+	(Action::Dump, &["d"]),
+	(Action::CheckWinnable, &["w"]),
+	(Action::Help, &["?", "f1"]),
+	(Action::Quit, &["q"]),
+];
+
+// the presets only list what they change from the default, and keep the arrow keys working
+const VIM_KEYS: [(Action, &[&str]); 12] = [
+	(Action::Left, &["h", "left"]),
+	(Action::Right, &["l", "right"]),
+	(Action::Up, &["k", "up"]),
+	(Action::Down, &["j", "down"]),
+	(Action::JumpLeft, &["H", "shift-left"]),
+	(Action::JumpRight, &["L", "shift-right"]),
+	(Action::JumpUp, &["K", "shift-up"]),
+	(Action::JumpDown, &["J", "shift-down"]),
+	(Action::Select, &["enter", "space"]),
+	(Action::Cancel, &["backspace", "esc"]),
+	(Action::Hint, &["i"]),
+	(Action::Redo, &["U", "ctrl-r"]),
+];
+
+const WASD_KEYS: [(Action, &[&str]); 12] = [
+	(Action::Left, &["a", "left"]),
+	(Action::Right, &["d", "right"]),
+	(Action::Up, &["w", "up"]),
+	(Action::Down, &["s", "down"]),
+	(Action::JumpLeft, &["A", "shift-left"]),
+	(Action::JumpRight, &["D", "shift-right"]),
+	(Action::JumpUp, &["W", "shift-up"]),
+	(Action::JumpDown, &["S", "shift-down"]),
+	(Action::Select, &["enter", "space"]),
+	(Action::Statistics, &["i"]),
+	(Action::Dump, &["f"]),
+	(Action::CheckWinnable, &["c"]),
+];
+
+/// Special keys by the names used in keymap files.
+const KEY_NAMES: [(Key, &str); 28] = [
+	(Key::Enter, "enter"),
+	(Key::Tab, "tab"),
+	(Key::Backspace, "backspace"),
+	(Key::Esc, "esc"),
+	(Key::Left, "left"),
+	(Key::Right, "right"),
+	(Key::Up, "up"),
+	(Key::Down, "down"),
+	(Key::Ins, "insert"),
+	(Key::Del, "delete"),
+	(Key::Home, "home"),
+	(Key::End, "end"),
+	(Key::PageUp, "pageup"),
+	(Key::PageDown, "pagedown"),
+	(Key::PauseBreak, "pause"),
+	(Key::NumpadCenter, "numpad5"),
+	(Key::F1, "f1"),
+	(Key::F2, "f2"),
+	(Key::F3, "f3"),
+	(Key::F4, "f4"),
+	(Key::F5, "f5"),
+	(Key::F6, "f6"),
+	(Key::F7, "f7"),
+	(Key::F8, "f8"),
+	(Key::F9, "f9"),
+	(Key::F10, "f10"),
+	(Key::F11, "f11"),
+	(Key::F12, "f12"),
+];
+
+/// The keys bound to each action.
+#[derive(Clone, Debug)]
+pub struct Keymap {
+	bindings: BTreeMap<Action, Vec<Event>>,
+}
+
+impl Default for Keymap {
+	fn default() -> Self {
+		preset(PRESETS[0]).expect("The default preset exists")
+	}
+}
+
+impl Keymap {
+	/// The action bound to an event, if any.
+	pub fn action(&self, event: &Event) -> Option<Action> {
+		self.bindings.iter().find(|(_, events)| events.contains(event)).map(|(&action, _)| action)
+	}
+	/// Every action together with the keys bound to it, in the order of the help.
+	pub fn bindings(&self) -> impl Iterator<Item = (Action, &[Event])> {
+		self.bindings.iter().map(|(&action, events)| (action, &events[..]))
+	}
+	/// Lists the actions and their keys, one action per line.
+	pub fn help(&self) -> String {
+		let lines: Vec<(&str, String)> = self
+			.bindings()
+			.filter(|(_, events)| !events.is_empty())
+			.map(|(action, events)| (action.description(), events.iter().map(key_name).collect::<Vec<_>>().join(", ")))
+			.collect();
+		let width = lines.iter().map(|(description, _)| description.len()).max().unwrap_or(0);
+		lines.iter().map(|(description, keys)| format!("{:width$}  {}", description, keys, width = width)).collect::<Vec<_>>().join("\n")
+	}
+	/// Replaces the keys of an action.
+	fn bind(&mut self, action: Action, keys: &[impl AsRef<str>]) -> anyhow::Result<()> {
+		let events = keys.iter().map(|key| parse_key(key.as_ref())).collect::<anyhow::Result<_>>()?;
+		self.bindings.insert(action, events);
+		Ok(())
+	}
+	/// Fails if any key is bound to more than one action.
+	fn check(&self) -> anyhow::Result<()> {
+		for (action, events) in self.bindings() {
+			for event in events {
+				if let Some(other) = self.bindings().find(|&(other, others)| other != action && others.contains(event)) {
+					bail!("The key {} is bound to both {:?} and {:?}", key_name(event), action.name(), other.0.name());
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+fn preset(name: &str) -> Option<Keymap> {
+	let changes: &[(Action, &[&str])] = match name {
+		"default" => &[],
+		"vim" => &VIM_KEYS,
+		"wasd" => &WASD_KEYS,
+		_ => return None,
+	};
+	let mut keymap = Keymap { bindings: BTreeMap::new() };
+	for (action, keys) in DEFAULT_KEYS.iter().chain(changes) {
+		keymap.bind(*action, keys).expect("Preset keys are valid");
+	}
+	Some(keymap)
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeymapFile {
+	#[serde(default)]
+	preset: Option<String>,
+	#[serde(default)]
+	keys: BTreeMap<String, Vec<String>>,
+}
+
+/// The keymap file that is used when no keymap is given.
+pub fn config_path() -> anyhow::Result<PathBuf> {
+	let dirs = directories::ProjectDirs::from("", "", "sss").context("Could not find the home directory")?;
+	Ok(dirs.config_dir().join("keys.toml"))
+}
+
+/// Loads a keymap by the name of a preset or from the path of a keymap file.
+///
+/// Without a name, the keymap file in the configuration directory is used if there is one, and the default preset otherwise.
+pub fn load(name: Option<&str>) -> anyhow::Result<Keymap> {
+	let path = match name {
+		Some(name) => match preset(name) {
+			Some(keymap) => return Ok(keymap),
+			None => PathBuf::from(name),
+		},
+		None => match config_path()? {
+			path if path.is_file() => path,
+			_ => return Ok(Keymap::default()),
+		},
+	};
+	read(&path).with_context(|| format!("Invalid keymap {:?}", path))
+}
+
+fn read(path: &Path) -> anyhow::Result<Keymap> {
+	let text = std::fs::read_to_string(path).context("Could not read the file")?;
+	let file = match path.extension().and_then(|extension| extension.to_str()) {
+		Some("json") => serde_json::from_str(&text)?,
+		_ => toml::from_str(&text)?,
+	};
+	build(file)
+}
+
+fn build(file: KeymapFile) -> anyhow::Result<Keymap> {
+	let preset_name = file.preset.as_deref().unwrap_or(PRESETS[0]);
+	let mut keymap = preset(preset_name).with_context(|| format!("Unknown preset {:?}", preset_name))?;
+	for (name, keys) in &file.keys {
+		let action = Action::ALL.into_iter().find(|action| action.name() == name).with_context(|| format!("Unknown action {:?}", name))?;
+		keymap.bind(action, keys)?;
+	}
+	keymap.check()?;
+	Ok(keymap)
+}
+
+fn parse_key(key: &str) -> anyhow::Result<Event> {
+	let special = |name: &str| KEY_NAMES.iter().find(|(_, key_name)| key_name.eq_ignore_ascii_case(name)).map(|&(key, _)| key);
+	let single = |name: &str| {
+		let mut chars = name.chars();
+		chars.next().filter(|_| chars.next().is_none())
+	};
+	let event = if key == "space" {
+		Some(Event::Char(' '))
+	} else if let Some(c) = single(key) {
+		Some(Event::Char(c))
+	} else if let Some(key) = special(key) {
+		Some(Event::Key(key))
+	} else if let Some(rest) = key.strip_prefix("ctrl-") {
+		single(rest).map(Event::CtrlChar).or_else(|| special(rest).map(Event::Ctrl))
+	} else if let Some(rest) = key.strip_prefix("alt-") {
+		single(rest).map(Event::AltChar).or_else(|| special(rest).map(Event::Alt))
+	} else if let Some(rest) = key.strip_prefix("shift-") {
+		special(rest).map(Event::Shift)
+	} else {
+		None
+	};
+	event.with_context(|| format!("Unknown key {:?}", key))
+}
+
+/// The name of a key as it is written in keymap files.
+fn key_name(event: &Event) -> String {
+	let special = |key: &Key| KEY_NAMES.iter().find(|(named, _)| named == key).map_or("?", |(_, name)| name);
+	match event {
+		Event::Char(' ') => "space".to_owned(),
+		Event::Char(c) => c.to_string(),
+		Event::CtrlChar(c) => format!("ctrl-{}", c),
+		Event::AltChar(c) => format!("alt-{}", c),
+		Event::Key(key) => special(key).to_owned(),
+		Event::Ctrl(key) => format!("ctrl-{}", special(key)),
+		Event::Alt(key) => format!("alt-{}", special(key)),
+		Event::Shift(key) => format!("shift-{}", special(key)),
+		other => format!("{:?}", other),
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn presets() {
+		for name in PRESETS {
+			let keymap = preset(name).unwrap();
+			keymap.check().unwrap();
+			for (_, events) in keymap.bindings() {
+				assert!(events.iter().all(|event| parse_key(&key_name(event)).ok().as_ref() == Some(event)), "{}", name);
+			}
+		}
+		assert_eq!(preset("vim").unwrap().action(&Event::Char('j')), Some(Action::Down));
+		assert_eq!(preset("wasd").unwrap().action(&Event::Char('w')), Some(Action::Up));
+		assert_eq!(Keymap::default().action(&Event::Key(Key::Left)), Some(Action::Left));
+	}
+	#[test]
+	fn keymap_files() {
+		let file: KeymapFile = toml::from_str("preset = \"vim\"\n[keys]\nhint = [\"?\"]\nhelp = [\"f1\"]\nquit = [\"q\", \"ctrl-c\"]\n").unwrap();
+		let keymap = build(file).unwrap();
+		assert_eq!(keymap.action(&Event::Char('?')), Some(Action::Hint));
+		assert_eq!(keymap.action(&Event::CtrlChar('c')), Some(Action::Quit));
+		assert_eq!(keymap.action(&Event::Char('h')), Some(Action::Left));
+
+		let conflict: KeymapFile = serde_json::from_str(r#"{"keys": {"hint": ["u"]}}"#).unwrap();
+		assert!(build(conflict).is_err());
+		let unknown: KeymapFile = toml::from_str("[keys]\nhint = [\"ctrl-enterr\"]\n").unwrap();
+		assert!(build(unknown).is_err());
+		let typo: KeymapFile = toml::from_str("[keys]\nhnit = [\"x\"]\n").unwrap();
+		assert!(build(typo).is_err());
+	}
+}
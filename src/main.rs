@@ -1,36 +1,121 @@
+use anyhow::Context;
+use clap::Parser;
 use cursive::traits::Nameable;
-use cursive::views::LinearLayout;
+use cursive::views::{Dialog, LinearLayout, TextView};
 use cursive::{Cursive, CursiveExt};
+use std::panic::AssertUnwindSafe;
 
 mod board;
+mod cli;
 mod elapsed;
 mod ext;
+mod game;
+mod keymap;
 mod matrix;
+mod records;
+mod save;
+mod solve;
 mod theme;
 mod tile;
 
 fn main() -> anyhow::Result<()> {
-	let mut siv = Cursive::new();
+	let args = cli::Args::parse();
+	if let Some(path) = &args.solve {
+		return solve::run(path, args.rules());
+	}
 
-	siv.set_theme(theme::theme());
+	let seed = args.seed.unwrap_or_else(rand::random);
+	let settings = game::Settings {
+		size: args.size,
+		deal: args.deal,
+		tile_set: args.tile_set,
+		rules: args.rules(),
+		theme: args.theme.clone(),
+		monochrome: args.monochrome || std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()),
+	};
+	let (mut board, status) = match &args.load {
+		Some(path) => (board::Board::load(path, settings.rules, seed)?, format!("  {}", path.display())),
+		None => (board::Board::new(settings.size, settings.deal, settings.tile_set, settings.rules, seed)?, game::seed_label(seed)),
+	};
+	board.set_glyphs(args.glyphs);
+	board.set_zoom(args.zoom);
+	// report a broken theme or keymap file before the interface takes over the terminal
+	theme::load(&settings.theme, true)?;
+	let keymap = keymap::load(args.keys.as_deref())?;
+	board.set_keymap(keymap.clone());
+
+	// only offer to resume when the player did not ask for a particular board
+	let saved = if args.load.is_none() && args.seed.is_none() { save::read() } else { Ok(None) };
+
+	// this has to happen before any other thread is started, so that the signals are only delivered to this thread
+	let signal_guard = graceful::SignalGuard::new();
+	let (sink_sender, sink) = std::sync::mpsc::channel();
+	let ui = std::thread::spawn(move || {
+		let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+			let mut siv = Cursive::new();
+			sink_sender.send(siv.cb_sink().clone()).expect("Main thread is waiting for the sink");
+			run_ui(&mut siv, board, status, settings, &keymap, saved);
+		}));
+		// the main thread is still waiting for a signal, so the process has to be ended from here
+		std::process::exit(if result.is_ok() { 0 } else { 101 });
+	});
+	let sink = sink.recv().context("The interface failed to start")?;
+	signal_guard.at_exit(move |_signal| {
+		let _ = sink.send(Box::new(|siv| {
+			// there is nobody to report an error to at this point
+			let _ = game::save(siv);
+			siv.quit();
+		}));
+		let _ = ui.join();
+	});
+
+	Ok(())
+}
+
+fn run_ui(siv: &mut Cursive, board: board::Board, status: String, settings: game::Settings, keymap: &keymap::Keymap, saved: anyhow::Result<Option<save::SavedGame>>) {
+	// the backend only starts with the event loop, so the terminal's colours are checked from inside it
+	siv.cb_sink()
+		.send(Box::new(|siv| {
+			if !theme::has_colors() {
+				siv.with_user_data(|settings: &mut game::Settings| settings.monochrome = true);
+				game::apply_theme(siv).expect("The theme was loaded before");
+			}
+		}))
+		.expect("The event loop has not started yet");
+	let rules = settings.rules;
+	siv.set_user_data(settings);
 	siv.set_autorefresh(true);
 	siv.add_fullscreen_layer({
-		let board = board::Board::default().with_name("board");
-		let board = board::CenterView::new(board);
+		let board = board::CenterView::new(board.with_name("board"));
 		let elapsed = elapsed::Elapsed::new().with_name("elapsed");
+		let score = TextView::new(game::score_label(0)).with_name("score");
+		let rules = TextView::new(game::rules_label(rules)).with_name("rules");
+		let status = TextView::new(status).with_name("status");
 		let mut ret = LinearLayout::vertical();
 		ret.add_child(board);
-		ret.add_child(elapsed);
+		ret.add_child(LinearLayout::horizontal().child(elapsed).child(score).child(rules).child(status));
 		ret
 	});
-	siv.add_global_callback('q', |siv| {
-		siv.quit();
-	});
-	siv.add_global_callback('r', |siv| {
-		siv.call_on_name("board", |current_board| *current_board = board::Board::default());
-		siv.call_on_name("elapsed", |current_elapsed| *current_elapsed = elapsed::Elapsed::new());
-	});
+	// the board handles its own actions, and these are the ones it leaves to the game
+	for (action, events) in keymap.bindings() {
+		let callback: fn(&mut Cursive) = match action {
+			keymap::Action::Quit => game::quit,
+			keymap::Action::Pause => game::toggle_pause,
+			keymap::Action::Restart => game::new_game,
+			keymap::Action::NewGame => game::new_game_dialog,
+			keymap::Action::Statistics => game::statistics,
+			keymap::Action::Theme => game::next_theme,
+			_ => continue,
+		};
+		for event in events {
+			siv.add_global_callback(event.clone(), callback);
+		}
+	}
+	game::apply_theme(siv).expect("The theme was loaded before");
+	match saved {
+		Ok(Some(saved)) => game::offer_resume(siv, saved),
+		Ok(None) => (),
+		Err(error) => siv.add_layer(Dialog::info(format!("Could not load the saved game: {:#}", error)).title("Error")),
+	}
 	siv.run();
-
-	Ok(())
 }
@@ -1,5 +1,6 @@
 use crate::ext::vec2::*;
 
+#[derive(Clone)]
 pub struct Matrix<T> {
 	size: Vec2,
 	data: Vec<T>,
@@ -16,6 +17,11 @@ impl<T> Matrix<T> {
 	pub fn rows(&self) -> impl Iterator<Item = &[T]> {
 		self.data.chunks_exact(self.size.width())
 	}
+	/// Iterates over every element along with its position, in row-major order.
+	pub fn iter(&self) -> impl Iterator<Item = (Vec2, &T)> {
+		let width = self.size.width();
+		self.data.iter().enumerate().map(move |(index, element)| (Vec2::new(index % width, index / width), element))
+	}
 
 	fn index(&self, position: Vec2) -> Option<usize> {
 		if self.size.contains(position) {
@@ -42,17 +48,17 @@ impl<T> Matrix<T> {
 	where
 		T: Copy, // could be Clone if we didn't use `self.rows()` but that's unnecessary and overcomplicated
 	{
-		use std::iter::repeat;
+		use std::iter::repeat_n;
 
 		self.data = {
 			let mut data = Vec::with_capacity(self.data.capacity() + (self.size().width() * 2) + (self.size().height() * 2) + 4);
-			data.extend(repeat(element).take(self.size.width() + 2));
+			data.extend(repeat_n(element, self.size.width() + 2));
 			for row in self.rows() {
 				data.push(element);
 				data.extend(row.iter().copied());
 				data.push(element);
 			}
-			data.extend(repeat(element).take(self.size.width() + 2));
+			data.extend(repeat_n(element, self.size.width() + 2));
 			data
 		};
 		self.size.x += 2;
@@ -0,0 +1,160 @@
+//! The results of past games, kept on disk for the statistics screen.
+
+use crate::board::Stats;
+use crate::save;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Outcome {
+	Won,
+	/// The player gave up when there were no moves left.
+	Lost,
+	/// A new game was started before this one was over.
+	Abandoned,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Record {
+	/// The size of the board without the border.
+	pub size: (usize, usize),
+	pub seed: u64,
+	pub time: Duration,
+	pub stats: Stats,
+	// records from before scoring was added count as zero
+	#[serde(default)]
+	pub score: i64,
+	pub outcome: Outcome,
+}
+
+fn path() -> anyhow::Result<PathBuf> {
+	Ok(save::data_dir()?.join("records.jsonl"))
+}
+
+/// Adds a record to the end of the file, one JSON object per line.
+pub fn append(record: &Record) -> anyhow::Result<()> {
+	let path = path()?;
+	std::fs::create_dir_all(path.parent().expect("Records path has a parent")).context("Could not create the data directory")?;
+	let mut file = std::fs::OpenOptions::new().create(true).append(true).open(&path).with_context(|| format!("Could not open {:?}", path))?;
+	let line = serde_json::to_string(record).expect("Records can always be serialized");
+	writeln!(file, "{}", line).with_context(|| format!("Could not write {:?}", path))
+}
+
+/// Reads all records, oldest first.
+pub fn read() -> anyhow::Result<Vec<Record>> {
+	let path = path()?;
+	let text = match std::fs::read_to_string(&path) {
+		Ok(text) => text,
+		Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+		Err(error) => return Err(error).with_context(|| format!("Could not read {:?}", path)),
+	};
+	text.lines()
+		.enumerate()
+		.map(|(index, line)| serde_json::from_str(line).with_context(|| format!("Line {} of {:?} is not a valid record", index + 1, path)))
+		.collect()
+}
+
+/// Totals over all recorded games.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+	pub played: usize,
+	pub won: usize,
+	/// Consecutive wins up to the most recent game.
+	pub current_streak: usize,
+	pub longest_streak: usize,
+	pub by_size: BTreeMap<(usize, usize), Best>,
+}
+
+/// The best results among the games won on one board size.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Best {
+	pub time: Duration,
+	pub score: i64,
+	pub wins: usize,
+}
+
+impl Summary {
+	pub fn new(records: &[Record]) -> Self {
+		let mut ret = Self::default();
+		for record in records {
+			ret.played += 1;
+			if record.outcome != Outcome::Won {
+				ret.current_streak = 0;
+				continue;
+			}
+			ret.won += 1;
+			ret.current_streak += 1;
+			ret.longest_streak = ret.longest_streak.max(ret.current_streak);
+			let best = ret.by_size.entry(record.size).or_insert(Best {
+				time: record.time,
+				score: record.score,
+				wins: 0,
+			});
+			best.time = best.time.min(record.time);
+			best.score = best.score.max(record.score);
+			best.wins += 1;
+		}
+		ret
+	}
+	/// The percentage of games that were won.
+	pub fn win_rate(&self) -> usize {
+		(self.won * 100).checked_div(self.played).unwrap_or(0)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::{Best, Outcome, Record, Summary};
+	use std::time::Duration;
+
+	fn record(size: (usize, usize), seconds: u64, outcome: Outcome) -> Record {
+		Record {
+			size,
+			seed: 0,
+			time: Duration::from_secs(seconds),
+			stats: Default::default(),
+			score: 1000 - seconds as i64,
+			outcome,
+		}
+	}
+
+	#[test]
+	fn summary() {
+		use Outcome::*;
+		let records = [
+			record((18, 8), 300, Won),
+			record((18, 8), 200, Won),
+			record((6, 4), 50, Won),
+			record((18, 8), 100, Lost),
+			record((18, 8), 400, Won),
+			record((18, 8), 10, Abandoned),
+		];
+		let summary = Summary::new(&records);
+		assert_eq!(summary.played, 6);
+		assert_eq!(summary.won, 4);
+		assert_eq!(summary.win_rate(), 66);
+		assert_eq!(summary.current_streak, 0);
+		assert_eq!(summary.longest_streak, 3);
+		assert_eq!(
+			summary.by_size[&(18, 8)],
+			Best {
+				time: Duration::from_secs(200),
+				score: 800,
+				wins: 3
+			}
+		);
+		assert_eq!(
+			summary.by_size[&(6, 4)],
+			Best {
+				time: Duration::from_secs(50),
+				score: 950,
+				wins: 1
+			}
+		);
+		assert_eq!(Summary::new(&[]).win_rate(), 0);
+	}
+}
@@ -0,0 +1,49 @@
+//! Keeps the game in progress on disk between runs.
+
+use crate::board::SavedBoard;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedGame {
+	pub board: SavedBoard,
+	pub elapsed: Duration,
+}
+
+/// The directory for files that persist between runs, such as the saved game.
+pub fn data_dir() -> anyhow::Result<PathBuf> {
+	let dirs = directories::ProjectDirs::from("", "", "sss").context("Could not find the home directory")?;
+	Ok(dirs.data_dir().to_owned())
+}
+
+fn path() -> anyhow::Result<PathBuf> {
+	Ok(data_dir()?.join("save.json"))
+}
+
+pub fn write(game: &SavedGame) -> anyhow::Result<()> {
+	let path = path()?;
+	std::fs::create_dir_all(path.parent().expect("Save path has a parent")).context("Could not create the data directory")?;
+	let json = serde_json::to_string(game).expect("Saved games can always be serialized");
+	std::fs::write(&path, json).with_context(|| format!("Could not write {:?}", path))
+}
+
+/// Reads the saved game, if there is one.
+pub fn read() -> anyhow::Result<Option<SavedGame>> {
+	let path = path()?;
+	let json = match std::fs::read_to_string(&path) {
+		Ok(json) => json,
+		Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+		Err(error) => return Err(error).with_context(|| format!("Could not read {:?}", path)),
+	};
+	serde_json::from_str(&json).with_context(|| format!("{:?} is not a valid saved game", path)).map(Some)
+}
+
+pub fn remove() -> anyhow::Result<()> {
+	let path = path()?;
+	match std::fs::remove_file(&path) {
+		Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error).with_context(|| format!("Could not remove {:?}", path)),
+		_ => Ok(()),
+	}
+}
@@ -0,0 +1,25 @@
+use crate::board::{text, Rules, Solution};
+use anyhow::bail;
+use std::path::Path;
+
+/// Reads a board from a text file and prints a sequence of moves that clears it under `rules`.
+///
+/// Fails if the board cannot be cleared, and also if the solver gives up because the board is too hard to decide within its budget.
+/// In that case the board may or may not be solvable, and the message says the solver gave up rather than that it is unsolvable.
+pub fn run(path: &Path, rules: Rules) -> anyhow::Result<()> {
+	let mut tiles = text::read(path)?;
+	rules.add_border(&mut tiles);
+	let border = rules.border();
+	match tiles.solve(rules.max_turns) {
+		Solution::Found(solution) => {
+			for (start, end) in solution {
+				let tile = tiles.get(start).unwrap().unwrap();
+				// undo the offset from the border so coordinates match the file
+				println!("{} ({}, {}) - ({}, {})", tile, start.x - border, start.y - border, end.x - border, end.y - border);
+			}
+			Ok(())
+		}
+		Solution::Unsolvable => bail!("The board cannot be solved"),
+		Solution::Unknown => bail!("The solver gave up: the board is too hard to decide quickly, so it may or may not be solvable"),
+	}
+}
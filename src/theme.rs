@@ -1,21 +1,247 @@
-use cursive::theme::{BorderStyle, Color, Palette, PaletteColor, Theme};
+//! Themes for the interface and the board, bundled or loaded from TOML or JSON files.
+//!
+//! A theme file looks like this, with every entry optional:
+//!
+//! ```toml
+//! shadow = false
+//! borders = "simple" # or "outset" or "none"
+//!
+//! [colors]
+//! # cursive palette entries, such as background, view, primary or highlight
+//! primary = "white"
+//! # the colour of each suit: numbers, sticks, circles, winds, dragons and bonus
+//! sticks = "light green"
+//!
+//! [styles]
+//! # lists of effects (bold, italic, underline, reverse, dim, blink, strikethrough) and colours
+//! selection = ["reverse"]
+//! hint = ["underline"]
+//! path = ["bold", "yellow"]
+//! ```
 
-pub fn theme() -> Theme {
+use crate::tile::Suit;
+use anyhow::{bail, Context};
+use cursive::theme::{BorderStyle, Color, ColorStyle, Effect, Palette, PaletteColor, Style, Theme};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// The themes that come with the game, by name. The first one is the default.
+const BUNDLED: [(&str, &str); 4] = [
+	("default", include_str!("../themes/default.toml")),
+	("dark", include_str!("../themes/dark.toml")),
+	("light", include_str!("../themes/light.toml")),
+	("high-contrast", include_str!("../themes/high-contrast.toml")),
+];
+
+pub const DEFAULT: &str = BUNDLED[0].0;
+
+const PALETTE_COLORS: [PaletteColor; 11] = [
+	PaletteColor::Background,
+	PaletteColor::Shadow,
+	PaletteColor::View,
+	PaletteColor::Primary,
+	PaletteColor::Secondary,
+	PaletteColor::Tertiary,
+	PaletteColor::TitlePrimary,
+	PaletteColor::TitleSecondary,
+	PaletteColor::Highlight,
+	PaletteColor::HighlightInactive,
+	PaletteColor::HighlightText,
+];
+
+/// A theme for the interface together with the styles the board uses.
+#[derive(Clone)]
+pub struct Loaded {
+	pub theme: Theme,
+	pub board: BoardStyles,
+}
+
+/// How the board highlights tiles and draws paths.
+#[derive(Clone, Copy)]
+pub struct BoardStyles {
+	pub selection: Style,
+	pub hint: Style,
+	pub path: Style,
+}
+
+impl Default for BoardStyles {
+	fn default() -> Self {
+		Self {
+			selection: Effect::Reverse.into(),
+			hint: Effect::Underline.into(),
+			path: Style::none(),
+		}
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+	#[serde(default)]
+	shadow: bool,
+	#[serde(default)]
+	borders: Option<String>,
+	#[serde(default)]
+	colors: BTreeMap<String, String>,
+	#[serde(default)]
+	styles: StylesFile,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct StylesFile {
+	selection: Option<Vec<String>>,
+	hint: Option<Vec<String>>,
+	path: Option<Vec<String>>,
+}
+
+/// The directory searched for user themes.
+pub fn themes_dir() -> anyhow::Result<PathBuf> {
+	let dirs = directories::ProjectDirs::from("", "", "sss").context("Could not find the home directory")?;
+	Ok(dirs.config_dir().join("themes"))
+}
+
+/// Lists the names of the bundled themes followed by the user themes, which are the `.toml` and `.json` files in `themes_dir`.
+pub fn available() -> Vec<String> {
+	let mut user: Vec<String> = themes_dir()
+		.and_then(|dir| Ok(std::fs::read_dir(dir)?))
+		.map(|entries| {
+			entries
+				.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+				.filter(|path| is_theme_file(path))
+				.filter_map(|path| path.file_stem().and_then(|stem| stem.to_str()).map(str::to_owned))
+				.filter(|name| !BUNDLED.iter().any(|(bundled, _)| bundled == name))
+				.collect()
+		})
+		.unwrap_or_default();
+	user.sort();
+	BUNDLED.iter().map(|(name, _)| name.to_string()).chain(user).collect()
+}
+
+fn is_theme_file(path: &Path) -> bool {
+	matches!(path.extension().and_then(|extension| extension.to_str()), Some("toml" | "json"))
+}
+
+/// Loads a theme by the name of a bundled or user theme, or from the path of a theme file.
+///
+/// Without `colors`, every colour is left at the terminal's default and the board falls back to effects to tell suits apart.
+pub fn load(name: &str, colors: bool) -> anyhow::Result<Loaded> {
+	if let Some((_, text)) = BUNDLED.iter().find(|(bundled, _)| *bundled == name) {
+		let file = toml::from_str(text).expect("Bundled themes are valid");
+		return Ok(build(file, colors).expect("Bundled themes are valid"));
+	}
+	let user = ["toml", "json"]
+		.into_iter()
+		.map(|extension| themes_dir().map(|dir| dir.join(name).with_extension(extension)))
+		.find(|path| path.as_ref().is_ok_and(|path| path.is_file()));
+	let path = match user {
+		Some(path) => path?,
+		None => PathBuf::from(name),
+	};
+	let text = std::fs::read_to_string(&path).with_context(|| format!("Could not read theme {:?}", path))?;
+	let file = match path.extension().and_then(|extension| extension.to_str()) {
+		Some("json") => serde_json::from_str(&text).map_err(anyhow::Error::from),
+		_ => toml::from_str(&text).map_err(anyhow::Error::from),
+	};
+	file.and_then(|file| build(file, colors)).with_context(|| format!("Invalid theme {:?}", path))
+}
+
+fn build(file: ThemeFile, colors: bool) -> anyhow::Result<Loaded> {
 	let mut palette = Palette::default();
-	palette[PaletteColor::Background] = Color::TerminalDefault;
-	palette[PaletteColor::Shadow] = Color::TerminalDefault;
-	palette[PaletteColor::View] = Color::TerminalDefault;
-	palette[PaletteColor::Primary] = Color::TerminalDefault;
-	palette[PaletteColor::Secondary] = Color::TerminalDefault;
-	palette[PaletteColor::Tertiary] = Color::TerminalDefault;
-	palette[PaletteColor::TitlePrimary] = Color::TerminalDefault;
-	palette[PaletteColor::TitleSecondary] = Color::TerminalDefault;
-	palette[PaletteColor::Highlight] = Color::TerminalDefault;
-	palette[PaletteColor::HighlightInactive] = Color::TerminalDefault;
-	palette[PaletteColor::HighlightText] = Color::TerminalDefault;
-	Theme {
-		shadow: false,
-		borders: BorderStyle::Simple,
-		palette,
+	for color in PALETTE_COLORS {
+		palette[color] = Color::TerminalDefault;
+	}
+	for (key, value) in &file.colors {
+		let color = parse_color(value)?;
+		// setting the entry to the default it already has doubles as a check that it exists
+		let is_suit = Suit::ALL.iter().any(|suit| suit.palette_key() == key);
+		if !is_suit && palette.set_basic_color(key, Color::TerminalDefault).is_err() {
+			bail!("Unknown color entry {:?}", key);
+		}
+		if colors {
+			palette.set_color(key, color);
+		}
+	}
+	let borders = match file.borders.as_deref() {
+		None | Some("simple") => BorderStyle::Simple,
+		Some("outset") => BorderStyle::Outset,
+		Some("none") => BorderStyle::None,
+		Some(other) => bail!("Unknown border style {:?}", other),
+	};
+	let defaults = BoardStyles::default();
+	let style = |words: Option<Vec<String>>, default: Style| match words {
+		Some(words) => parse_style(&words, colors),
+		None => Ok(default),
+	};
+	Ok(Loaded {
+		theme: Theme { shadow: file.shadow, borders, palette },
+		board: BoardStyles {
+			selection: style(file.styles.selection, defaults.selection)?,
+			hint: style(file.styles.hint, defaults.hint)?,
+			path: style(file.styles.path, defaults.path)?,
+		},
+	})
+}
+
+fn parse_color(value: &str) -> anyhow::Result<Color> {
+	Color::parse(value).with_context(|| format!("Unknown color {:?}", value))
+}
+
+/// Combines a list of effect names and colours into a style, leaving out the colours without `colors`.
+fn parse_style(words: &[String], colors: bool) -> anyhow::Result<Style> {
+	let mut style = Style::none();
+	for word in words {
+		style = match word.as_str() {
+			"bold" => style.combine(Effect::Bold),
+			"italic" => style.combine(Effect::Italic),
+			"underline" => style.combine(Effect::Underline),
+			"reverse" => style.combine(Effect::Reverse),
+			"dim" => style.combine(Effect::Dim),
+			"blink" => style.combine(Effect::Blink),
+			"strikethrough" => style.combine(Effect::Strikethrough),
+			color => {
+				let color = parse_color(color)?;
+				if colors {
+					style.combine(ColorStyle::front(color))
+				} else {
+					style
+				}
+			}
+		};
+	}
+	Ok(style)
+}
+
+/// Returns true if the terminal can show colours. This is only known once the interface has started.
+pub fn has_colors() -> bool {
+	cursive::backends::curses::n::ncurses::has_colors()
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn bundled_themes() {
+		for name in available().iter().take(BUNDLED.len()) {
+			let loaded = load(name, true).unwrap();
+			assert!(Suit::ALL.iter().all(|suit| loaded.theme.palette.custom(suit.palette_key()).is_some()), "{}", name);
+			let monochrome = load(name, false).unwrap();
+			assert!(Suit::ALL.iter().all(|suit| monochrome.theme.palette.custom(suit.palette_key()).is_none()), "{}", name);
+		}
+	}
+	#[test]
+	fn theme_files() {
+		let file: ThemeFile = serde_json::from_str(r##"{"borders": "none", "colors": {"view": "black", "winds": "#ff8800"}, "styles": {"path": ["bold", "light red"]}}"##).unwrap();
+		let loaded = build(file, true).unwrap();
+		assert_eq!(loaded.theme.borders, BorderStyle::None);
+		assert_eq!(loaded.theme.palette[PaletteColor::View], Color::parse("black").unwrap());
+		assert_eq!(loaded.theme.palette.custom("winds"), Some(&Color::Rgb(0xff, 0x88, 0x00)));
+		assert_eq!(loaded.board.path, Style::from(Effect::Bold).combine(ColorStyle::front(Color::parse("light red").unwrap())));
+
+		let typo: ThemeFile = toml::from_str("[colors]\nnumbres = \"red\"\n").unwrap();
+		assert!(build(typo, true).is_err());
+		assert!(toml::from_str::<ThemeFile>("border = \"none\"\n").is_err());
 	}
 }
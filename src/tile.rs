@@ -1,5 +1,5 @@
 #[repr(u8)]
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, serde::Serialize, serde::Deserialize)]
 pub enum Tile {
 	Blank,
 	Number1,
@@ -37,10 +37,18 @@ pub enum Tile {
 	DragonRedDown,
 	DragonGreen,
 	DragonBlue,
+	FlowerPlum,
+	FlowerOrchid,
+	FlowerBamboo,
+	FlowerChrysanthemum,
+	SeasonSpring,
+	SeasonSummer,
+	SeasonAutumn,
+	SeasonWinter,
 }
 
 impl Tile {
-	pub const NUM_TILES: usize = 36;
+	pub const NUM_TILES: usize = 44;
 	pub const fn all() -> [Tile; Self::NUM_TILES] {
 		use Tile::*;
 		[
@@ -80,9 +88,43 @@ impl Tile {
 			DragonRedDown,
 			DragonGreen,
 			DragonBlue,
+			FlowerPlum,
+			FlowerOrchid,
+			FlowerBamboo,
+			FlowerChrysanthemum,
+			SeasonSpring,
+			SeasonSummer,
+			SeasonAutumn,
+			SeasonWinter,
 		]
 	}
 
+	/// The tile standing in for every tile this one matches: any flower matches any other flower and any season any other season, while every other tile only matches itself.
+	pub const fn group(self) -> Tile {
+		use Tile::*;
+		match self {
+			FlowerPlum | FlowerOrchid | FlowerBamboo | FlowerChrysanthemum => FlowerPlum,
+			SeasonSpring | SeasonSummer | SeasonAutumn | SeasonWinter => SeasonSpring,
+			other => other,
+		}
+	}
+	pub const fn suit(self) -> Suit {
+		use Tile::*;
+		match self {
+			Number1 | Number2 | Number3 | Number4 | Number5 | Number6 | Number7 | Number8 | Number9 => Suit::Numbers,
+			Sticks1 | Sticks2 | Sticks3 | Sticks4 | Sticks5 | Sticks6 | Sticks7 | Sticks8 | Sticks9 => Suit::Sticks,
+			Circles1 | Circles2 | Circles3 | Circles4 | Circles5 | Circles6 | Circles7 | Circles8 | Circles9 => Suit::Circles,
+			WindNorth | WindEast | WindWest | WindSouth => Suit::Winds,
+			// the blank tile doubles as the white dragon
+			Blank | DragonRedUp | DragonRedDown | DragonGreen | DragonBlue => Suit::Dragons,
+			FlowerPlum | FlowerOrchid | FlowerBamboo | FlowerChrysanthemum | SeasonSpring | SeasonSummer | SeasonAutumn | SeasonWinter => Suit::Bonus,
+		}
+	}
+	/// Returns true if the two tiles can be removed as a pair.
+	pub fn matches(self, other: Tile) -> bool {
+		self.group() == other.group()
+	}
+
 	pub const fn repr(self) -> &'static str {
 		use Tile::*;
 		match self {
@@ -122,6 +164,66 @@ impl Tile {
 			DragonRedDown => "}",
 			DragonGreen => "$",
 			DragonBlue => "&",
+			FlowerPlum => "p",
+			FlowerOrchid => "q",
+			FlowerBamboo => "r",
+			FlowerChrysanthemum => "s",
+			SeasonSpring => "P",
+			SeasonSummer => "Q",
+			SeasonAutumn => "R",
+			SeasonWinter => "S",
+		}
+	}
+	/// The character from the Unicode Mahjong Tiles block that shows this tile.
+	///
+	/// The blank tile is drawn as the white dragon, which is traditionally blank. The block has no second red dragon or blue dragon, so those borrow the joker and the tile back.
+	pub const fn glyph(self) -> &'static str {
+		use Tile::*;
+		match self {
+			Blank => "\u{1f006}",
+			Number1 => "\u{1f007}",
+			Number2 => "\u{1f008}",
+			Number3 => "\u{1f009}",
+			Number4 => "\u{1f00a}",
+			Number5 => "\u{1f00b}",
+			Number6 => "\u{1f00c}",
+			Number7 => "\u{1f00d}",
+			Number8 => "\u{1f00e}",
+			Number9 => "\u{1f00f}",
+			Sticks1 => "\u{1f010}",
+			Sticks2 => "\u{1f011}",
+			Sticks3 => "\u{1f012}",
+			Sticks4 => "\u{1f013}",
+			Sticks5 => "\u{1f014}",
+			Sticks6 => "\u{1f015}",
+			Sticks7 => "\u{1f016}",
+			Sticks8 => "\u{1f017}",
+			Sticks9 => "\u{1f018}",
+			Circles1 => "\u{1f019}",
+			Circles2 => "\u{1f01a}",
+			Circles3 => "\u{1f01b}",
+			Circles4 => "\u{1f01c}",
+			Circles5 => "\u{1f01d}",
+			Circles6 => "\u{1f01e}",
+			Circles7 => "\u{1f01f}",
+			Circles8 => "\u{1f020}",
+			Circles9 => "\u{1f021}",
+			WindNorth => "\u{1f003}",
+			WindEast => "\u{1f000}",
+			WindWest => "\u{1f002}",
+			WindSouth => "\u{1f001}",
+			DragonRedUp => "\u{1f004}",
+			DragonRedDown => "\u{1f02a}",
+			DragonGreen => "\u{1f005}",
+			DragonBlue => "\u{1f02b}",
+			FlowerPlum => "\u{1f022}",
+			FlowerOrchid => "\u{1f023}",
+			FlowerBamboo => "\u{1f024}",
+			FlowerChrysanthemum => "\u{1f025}",
+			SeasonSpring => "\u{1f026}",
+			SeasonSummer => "\u{1f027}",
+			SeasonAutumn => "\u{1f028}",
+			SeasonWinter => "\u{1f029}",
 		}
 	}
 }
@@ -131,3 +233,136 @@ impl std::fmt::Display for Tile {
 		formatter.write_str(self.repr())
 	}
 }
+
+/// The families of tiles, which are drawn in different colours.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Suit {
+	Numbers,
+	Sticks,
+	Circles,
+	Winds,
+	Dragons,
+	/// Flowers and seasons.
+	Bonus,
+}
+
+impl Suit {
+	pub const ALL: [Suit; 6] = [Suit::Numbers, Suit::Sticks, Suit::Circles, Suit::Winds, Suit::Dragons, Suit::Bonus];
+
+	/// The name of the custom palette entry holding the colour of this suit.
+	pub const fn palette_key(self) -> &'static str {
+		match self {
+			Self::Numbers => "numbers",
+			Self::Sticks => "sticks",
+			Self::Circles => "circles",
+			Self::Winds => "winds",
+			Self::Dragons => "dragons",
+			Self::Bonus => "bonus",
+		}
+	}
+	/// Returns true for the winds and dragons, which are drawn in bold when there are no colours to tell the suits apart.
+	pub const fn is_honor(self) -> bool {
+		matches!(self, Self::Winds | Self::Dragons)
+	}
+}
+
+/// How tiles are drawn on screen.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, clap::ValueEnum)]
+pub enum Glyphs {
+	/// One ASCII character per tile, as in the text format.
+	#[default]
+	Ascii,
+	/// The tiles from the Unicode Mahjong Tiles block, two columns wide.
+	Unicode,
+}
+
+impl Glyphs {
+	/// How many terminal columns each cell of the board takes up.
+	///
+	/// Most terminals draw mahjong tiles two columns wide, even though Unicode only gives that width to the red dragon.
+	pub const fn width(self) -> usize {
+		match self {
+			Self::Ascii => 1,
+			Self::Unicode => 2,
+		}
+	}
+	pub const fn show(self, tile: Tile) -> &'static str {
+		match self {
+			Self::Ascii => tile.repr(),
+			Self::Unicode => tile.glyph(),
+		}
+	}
+}
+
+/// Which tiles a new board is dealt from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, clap::ValueEnum)]
+pub enum TileSet {
+	/// Four of each of the 36 kinds of tile.
+	#[default]
+	Classic,
+	/// The traditional 144 tiles: four of each of the 34 standard kinds, plus the four flowers and the four seasons.
+	Traditional,
+}
+
+impl TileSet {
+	/// The groups of four tiles that make up one full set, where the tiles in each group all match each other.
+	pub fn groups(self) -> Vec<[Tile; 4]> {
+		use Tile::*;
+		let standard = Tile::all().into_iter().filter(|tile| !matches!(tile.group(), FlowerPlum | SeasonSpring));
+		match self {
+			Self::Classic => standard.map(|tile| [tile; 4]).collect(),
+			Self::Traditional => standard
+				// the blank tile doubles as the white dragon, so only the red and green dragons are left
+				.filter(|tile| !matches!(tile, DragonRedDown | DragonBlue))
+				.map(|tile| [tile; 4])
+				.chain([[FlowerPlum, FlowerOrchid, FlowerBamboo, FlowerChrysanthemum], [SeasonSpring, SeasonSummer, SeasonAutumn, SeasonWinter]])
+				.collect(),
+		}
+	}
+}
+
+/// The inverse of `repr`.
+impl std::str::FromStr for Tile {
+	type Err = anyhow::Error;
+
+	fn from_str(repr: &str) -> anyhow::Result<Self> {
+		Self::all().into_iter().find(|tile| tile.repr() == repr).ok_or_else(|| anyhow::anyhow!("Unknown tile {:?}", repr))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::Tile;
+
+	#[test]
+	fn parse_repr() {
+		for tile in Tile::all() {
+			assert_eq!(tile.repr().parse::<Tile>().unwrap(), tile);
+		}
+		assert!("x".parse::<Tile>().is_err());
+		assert!("12".parse::<Tile>().is_err());
+		assert!("".parse::<Tile>().is_err());
+	}
+	#[test]
+	fn glyphs() {
+		use std::collections::HashSet;
+
+		let glyphs: HashSet<&str> = Tile::all().into_iter().map(Tile::glyph).collect();
+		assert_eq!(glyphs.len(), Tile::NUM_TILES);
+		assert!(glyphs.iter().all(|glyph| glyph.chars().all(|c| ('\u{1f000}'..='\u{1f02b}').contains(&c))));
+	}
+	#[test]
+	fn tile_sets() {
+		use super::TileSet;
+
+		for set in [TileSet::Classic, TileSet::Traditional] {
+			let groups = set.groups();
+			assert_eq!(groups.len() * 4, 144);
+			assert!(groups.iter().all(|group| group.iter().all(|tile| tile.matches(group[0]))));
+			assert!(groups.iter().enumerate().all(|(index, group)| groups[index + 1..].iter().all(|other| !group[0].matches(other[0]))));
+		}
+		assert!(Tile::FlowerPlum.matches(Tile::FlowerBamboo));
+		assert!(!Tile::FlowerPlum.matches(Tile::SeasonSpring));
+		assert!(!Tile::Sticks1.matches(Tile::Sticks2));
+	}
+}